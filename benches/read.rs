@@ -0,0 +1,139 @@
+//! Throughput of the reader on generated files, in MB/s of file and rows/s.
+//! Compare runs with `cargo bench -- --save-baseline main` and
+//! `cargo bench -- --baseline main`.
+use std::io::Cursor;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sas::{rdc_decompress, rle_decompress, SAS7bdat};
+use sas::fixture::*;
+
+struct Dataset{
+    name : &'static str,
+    rows : usize,
+    bytes : Vec<u8>,
+}
+
+fn numeric(rows : usize, compression : Compression) -> Fixture{
+    let mut f = Fixture{compression, ..Fixture::default()};
+    for j in 0..20{
+        f.columns.push(Column::numeric(&format!("x{j}")));
+    }
+    for i in 0..rows{
+        f.rows.push((0..20).map(|j| Cell::Num(i as f64 * 0.5 + j as f64)).collect());
+    }
+    f
+}
+
+fn strings(rows : usize, compression : Compression) -> Fixture{
+    let mut f = Fixture{compression, ..Fixture::default()};
+    for j in 0..10{
+        f.columns.push(Column::character(&format!("s{j}"), 32));
+    }
+    for i in 0..rows{
+        f.rows.push((0..10).map(|j| Cell::Str(format!("value {i} in column {j}"))).collect());
+    }
+    f
+}
+
+fn wide(rows : usize) -> Fixture{
+    let mut f = Fixture{page_len : 1 << 16, ..Fixture::default()};
+    for j in 0..1200{
+        f.columns.push(Column::numeric(&format!("w{j}")));
+    }
+    for i in 0..rows{
+        f.rows.push((0..1200).map(|j| Cell::Num((i * j) as f64)).collect());
+    }
+    f
+}
+
+fn dataset(name : &'static str, f : Fixture) -> Dataset{
+    Dataset{name, rows : f.rows.len(), bytes : f.to_bytes()}
+}
+
+fn read_all(bytes : &[u8]) -> usize{
+    let mut sas = SAS7bdat::from_reader(Cursor::new(bytes)).unwrap();
+    let mut n = 0;
+    while sas.read_line().unwrap(){
+        n += 1;
+    }
+    n
+}
+
+//Each file is measured twice, once per throughput unit, criterion only
+//reports one per group.
+fn decode_rows(c : &mut Criterion){
+    let sets = [
+        dataset("numeric", numeric(50_000, Compression::None)),
+        dataset("numeric_rle", numeric(50_000, Compression::Rle)),
+        dataset("numeric_rdc", numeric(50_000, Compression::Rdc)),
+        dataset("strings", strings(50_000, Compression::None)),
+        dataset("strings_rle", strings(50_000, Compression::Rle)),
+        dataset("strings_rdc", strings(50_000, Compression::Rdc)),
+        dataset("wide_1200", wide(1_000)),
+    ];
+    let mut group = c.benchmark_group("read_bytes");
+    for set in sets.iter(){
+        group.throughput(Throughput::BytesDecimal(set.bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(set.name), &set.bytes, |b, bytes| b.iter(|| read_all(bytes)));
+    }
+    group.finish();
+    let mut group = c.benchmark_group("read_rows");
+    for set in sets.iter(){
+        group.throughput(Throughput::Elements(set.rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(set.name), &set.bytes, |b, bytes| b.iter(|| read_all(bytes)));
+    }
+    group.finish();
+}
+
+fn iterator_vs_read_line(c : &mut Criterion){
+    let set = dataset("numeric", numeric(50_000, Compression::None));
+    let mut group = c.benchmark_group("iterator");
+    group.throughput(Throughput::Elements(set.rows as u64));
+    group.bench_function("read_line", |b| b.iter(|| read_all(&set.bytes)));
+    group.bench_function("iterator", |b| b.iter(|| {
+        SAS7bdat::from_reader(Cursor::new(set.bytes.as_slice())).unwrap().map(|x| x.unwrap().len()).sum::<usize>()
+    }));
+    group.bench_function("read_batch", |b| b.iter(|| {
+        let mut sas = SAS7bdat::from_reader(Cursor::new(set.bytes.as_slice())).unwrap();
+        let mut n = 0;
+        loop{
+            let batch = sas.read_batch(1024).unwrap();
+            if batch.is_empty(){
+                break n;
+            }
+            n += batch.len();
+        }
+    }));
+    group.finish();
+}
+
+fn decompress(c : &mut Criterion){
+    let f = strings(5_000, Compression::None);
+    let mut f_num = numeric(5_000, Compression::None);
+    f_num.columns.truncate(10);
+    let rows : Vec<Vec<u8>> = f.rows.iter().map(|x| f.encode_row(x))
+        .chain(f_num.rows.iter().map(|x| f_num.encode_row(x)))
+        .collect();
+    let total : usize = rows.iter().map(|x| x.len()).sum();
+    let rle : Vec<(usize, Vec<u8>)> = rows.iter().map(|x| (x.len(), rle_compress(x))).collect();
+    let rdc : Vec<(usize, Vec<u8>)> = rows.iter().map(|x| (x.len(), rdc_compress(x))).collect();
+
+    let mut group = c.benchmark_group("decompress");
+    group.throughput(Throughput::BytesDecimal(total as u64));
+    let mut out = Vec::new();
+    group.bench_function("rle", |b| b.iter(|| {
+        for (len, x) in rle.iter(){
+            out.resize(*len, 0);
+            rle_decompress(x, &mut out).unwrap();
+        }
+    }));
+    group.bench_function("rdc", |b| b.iter(|| {
+        for (len, x) in rdc.iter(){
+            out.resize(*len, 0);
+            rdc_decompress(x, &mut out).unwrap();
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, decode_rows, iterator_vs_read_line, decompress);
+criterion_main!(benches);
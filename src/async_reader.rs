@@ -0,0 +1,181 @@
+use std::io::{BufReader, Cursor, ErrorKind, SeekFrom};
+use futures_util::stream::{self, Stream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use crate::source::{Page, Source};
+use crate::*;
+
+/// Reads a sas7bdat file from a tokio reader. Only the I/O is asynchronous,
+/// the pages are decoded by a regular `SAS7bdat` holding the header.
+pub struct AsyncSAS7bdat<R>{
+    reader : R,
+    core : SAS7bdat<Cursor<Vec<u8>>>,
+}
+
+//Returns the number of read bytes, smaller than len at the end of the file.
+async fn read_page<R : AsyncRead + Unpin>(rdr : &mut R, page : &mut Page, len : usize) -> Result<usize, SasError>{
+    let buf = page.owned_mut();
+    buf.resize(len, 0);
+    let mut done = 0;
+    while done < len{
+        match rdr.read(&mut buf[done..]).await{
+            Ok(0) => break,
+            Ok(n) => done += n,
+            Err(er) if er.kind() == ErrorKind::Interrupted => (),
+            Err(er) => return Err(SasError::Io(er)),
+        }
+    }
+    Ok(done)
+}
+
+impl<R : AsyncRead + AsyncSeek + Unpin> AsyncSAS7bdat<R>{
+    /// Rewinds `reader` and parses the metadata of the file.
+    pub async fn new(mut reader : R) -> Result<AsyncSAS7bdat<R>, SasError>{
+        reader.seek(SeekFrom::Start(0)).await.map_err(SasError::Io)?;
+        AsyncSAS7bdat::from_reader(reader).await
+    }
+}
+
+impl<R : AsyncRead + Unpin> AsyncSAS7bdat<R>{
+    /// Parses the metadata of the file, reading strictly forward from the
+    /// current position of `reader`.
+    pub async fn from_reader(reader : R) -> Result<AsyncSAS7bdat<R>, SasError>{
+        ReadOptions::default().read_async(reader).await
+    }
+
+    async fn with_options(mut reader : R, opts : &ReadOptions) -> Result<AsyncSAS7bdat<R>, SasError>{
+        let mut hdr = Page::default();
+        if read_page(&mut reader, &mut hdr, 288).await? < 288 {
+            return Err(SasError::Read);
+        }
+        let mut buf = hdr.to_vec();
+        let (len, _) = checked_layout(&buf)?;
+        if len > buf.len(){
+            let mut rest = Page::default();
+            if read_page(&mut reader, &mut rest, len - buf.len()).await? < len - buf.len() {
+                return Err(SasError::SasProperty("Sas7BDAT file seems to be truncated".to_string()));
+            }
+            buf.extend_from_slice(&rest);
+        }
+
+        let mut core = SAS7bdat::with_header(Source::Stream(BufReader::new(Cursor::new(buf))), opts)?;
+        loop {
+            let n = read_page(&mut reader, &mut core.cached_page, core.props.page_len).await?;
+            if core.meta_page_read(n)?{
+                break;
+            }
+        }
+        core.end_metadata()?;
+        Ok(AsyncSAS7bdat{reader, core})
+    }
+
+    /// Reads the next row into `row_vals`. Returns false at the end of the
+    /// file.
+    pub async fn read_line(&mut self) -> Result<bool, SasError>{
+        loop {
+            while self.core.page_exhausted(){
+                let n = read_page(&mut self.reader, &mut self.core.cached_page, self.core.props.page_len).await?;
+                if self.core.data_page_read(n)?{
+                    return Ok(false);
+                }
+            }
+            if self.core.read_row_on_page()?{
+                return Ok(true);
+            }
+        }
+    }
+
+    pub fn row_vals(&self) -> &Vec<SasVal>{
+        &self.core.row_vals
+    }
+
+    pub fn name(&self) -> &str{
+        self.core.name()
+    }
+
+    pub fn row_count(&self) -> usize{
+        self.core.row_count()
+    }
+
+    pub fn col_names(&self) -> &Vec<String>{
+        self.core.col_names()
+    }
+
+    pub fn col_label(&self) -> &Vec<String>{
+        self.core.col_label()
+    }
+
+    pub fn col_formats(&self) -> &Vec<String>{
+        self.core.col_formats()
+    }
+
+    pub fn col_format_specs(&self) -> &Vec<ColumnFormat>{
+        self.core.col_format_specs()
+    }
+
+    pub fn encoding(&self) -> Encoding{
+        self.core.encoding()
+    }
+
+    pub fn decode_errors(&self) -> &[usize]{
+        self.core.decode_errors()
+    }
+
+    pub fn row_deleted(&self) -> bool{
+        self.core.row_deleted()
+    }
+
+    pub fn deleted_count(&self) -> usize{
+        self.core.deleted_count()
+    }
+
+    pub fn damage_report(&self) -> &DamageReport{
+        self.core.damage_report()
+    }
+
+    /// Turns the reader into a stream of rows.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Vec<SasVal>, SasError>>{
+        stream::unfold(Some(self), |state| async move {
+            let mut sas = state?;
+            match sas.read_line().await{
+                Ok(true) => {
+                    let row = sas.core.row_vals.clone();
+                    Some((Ok(row), Some(sas)))
+                }
+                Ok(false) => None,
+                Err(er) => Some((Err(er), None)),
+            }
+        })
+    }
+
+    /// Turns the reader into a stream of batches of at most `size` rows. On
+    /// an error, the rows read before it are yielded first, then the error.
+    pub fn into_batches(self, size : usize) -> impl Stream<Item = Result<Vec<Vec<SasVal>>, SasError>>{
+        let size = size.max(1);
+        stream::unfold((Some(self), None), move |(state, failed)| async move {
+            if let Some(er) = failed{
+                return Some((Err(er), (None, None)));
+            }
+            let mut sas = state?;
+            let mut batch = Vec::with_capacity(size);
+            while batch.len() < size{
+                match sas.read_line().await{
+                    Ok(true) => batch.push(sas.core.row_vals.clone()),
+                    Ok(false) => break,
+                    Err(er) if batch.is_empty() => return Some((Err(er), (None, None))),
+                    Err(er) => return Some((Ok(batch), (None, Some(er)))),
+                }
+            }
+            if batch.is_empty(){
+                return None;
+            }
+            Some((Ok(batch), (Some(sas), None)))
+        })
+    }
+}
+
+impl ReadOptions{
+    /// Reads a file from a tokio reader, see `AsyncSAS7bdat::from_reader`.
+    pub async fn read_async<R : AsyncRead + Unpin>(&self, reader : R) -> Result<AsyncSAS7bdat<R>, SasError>{
+        AsyncSAS7bdat::with_options(reader, self).await
+    }
+}
@@ -0,0 +1,317 @@
+use std::io::Read;
+use crate::*;
+
+//Days and seconds from 1960-01-01, the SAS epoch, to 1970-01-01.
+const EPOCH_DAYS : i32 = 3653;
+const EPOCH_SECS : f64 = 315_619_200.0;
+
+/// Bit `i` is set if value `i` is present, least significant bit first as in
+/// Arrow.
+#[derive(Clone, Debug, Default)]
+pub struct Validity{
+    bits : Vec<u8>,
+    len : usize,
+}
+
+impl Validity{
+    pub fn is_valid(&self, i : usize) -> bool{
+        self.bits[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    pub fn bits(&self) -> &[u8]{
+        &self.bits
+    }
+
+    pub fn len(&self) -> usize{
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len == 0
+    }
+
+    pub fn null_count(&self) -> usize{
+        (0..self.len).filter(|i| !self.is_valid(*i)).count()
+    }
+
+    fn push(&mut self, valid : bool){
+        if self.len.is_multiple_of(8){
+            self.bits.push(0);
+        }
+        if valid{
+            self.bits[self.len / 8] |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    fn truncate(&mut self, len : usize){
+        if len >= self.len{
+            return;
+        }
+        self.len = len;
+        self.bits.truncate(len.div_ceil(8));
+        if !len.is_multiple_of(8){
+            self.bits[len / 8] &= (1 << (len % 8)) - 1;
+        }
+    }
+}
+
+/// The values of one column of a `ColumnBatch`. Missing numerics (NaN) are
+/// unset in the validity bitmap, as are dates and times out of the range of
+/// their type. Their value is 0 for temporal columns.
+#[derive(Clone, Debug)]
+pub enum BatchColumn{
+    Numeric{values : Vec<f64>, validity : Validity},
+    /// Days since 1970-01-01.
+    Date{days : Vec<i32>, validity : Validity},
+    /// Microseconds since 1970-01-01 00:00:00 UTC.
+    DateTime{micros : Vec<i64>, validity : Validity},
+    /// Microseconds since midnight, durations may exceed a day.
+    Time{micros : Vec<i64>, validity : Validity},
+    /// Value `i` is `bytes[offsets[i]..offsets[i + 1]]`, utf-8 with trailing
+    /// blanks removed, or the raw bytes with `DecodePolicy::Raw`.
+    Text{offsets : Vec<usize>, bytes : Vec<u8>},
+}
+
+impl BatchColumn{
+    fn new(op : Option<CellOp>, cap : usize) -> BatchColumn{
+        let validity = Validity{bits : Vec::with_capacity(cap.div_ceil(8)), len : 0};
+        match op{
+            Some(CellOp::Date(_)) => BatchColumn::Date{days : Vec::with_capacity(cap), validity},
+            Some(CellOp::DateTime(_)) => BatchColumn::DateTime{micros : Vec::with_capacity(cap), validity},
+            Some(CellOp::Time(_)) => BatchColumn::Time{micros : Vec::with_capacity(cap), validity},
+            Some(CellOp::Text) => {
+                let mut offsets = Vec::with_capacity(cap + 1);
+                offsets.push(0);
+                BatchColumn::Text{offsets, bytes : Vec::new()}
+            }
+            //columns behind a column without bytes are not decoded and stay
+            //missing, see SAS7bdat::decode_plan
+            Some(CellOp::Num(_)) | None => BatchColumn::Numeric{values : Vec::with_capacity(cap), validity},
+        }
+    }
+
+    /// Text of value `i` of a text column.
+    pub fn text(&self, i : usize) -> Option<&[u8]>{
+        match self{
+            BatchColumn::Text{offsets, bytes} => Some(&bytes[offsets[i]..offsets[i + 1]]),
+            _ => None,
+        }
+    }
+
+    fn truncate(&mut self, len : usize){
+        match self{
+            BatchColumn::Numeric{values, validity} => {
+                values.truncate(len);
+                validity.truncate(len);
+            }
+            BatchColumn::Date{days, validity} => {
+                days.truncate(len);
+                validity.truncate(len);
+            }
+            BatchColumn::DateTime{micros, validity} | BatchColumn::Time{micros, validity} => {
+                micros.truncate(len);
+                validity.truncate(len);
+            }
+            BatchColumn::Text{offsets, bytes} => {
+                offsets.truncate(len + 1);
+                bytes.truncate(offsets[len]);
+            }
+        }
+    }
+
+    fn push_missing(&mut self){
+        match self{
+            BatchColumn::Numeric{values, validity} => {
+                values.push(f64::NAN);
+                validity.push(false);
+            }
+            BatchColumn::Date{days, validity} => {
+                days.push(0);
+                validity.push(false);
+            }
+            BatchColumn::DateTime{micros, validity} | BatchColumn::Time{micros, validity} => {
+                micros.push(0);
+                validity.push(false);
+            }
+            BatchColumn::Text{offsets, bytes} => offsets.push(bytes.len()),
+        }
+    }
+}
+
+/// Rows read by `SAS7bdat::read_batch`, one typed buffer per column in the
+/// order of `col_names`, or of the selected columns.
+#[derive(Clone, Debug)]
+pub struct ColumnBatch{
+    pub columns : Vec<BatchColumn>,
+    //index of each column in col_names
+    cols : Vec<usize>,
+    len : usize,
+}
+
+impl ColumnBatch{
+    /// Number of rows.
+    pub fn len(&self) -> usize{
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.len == 0
+    }
+
+    //Appends the row in src, or nothing if a cell cannot be decoded.
+    pub(crate) fn push_row(&mut self, plan : &[ColPlan], src : &[u8], text : &TextOpts, errors : &mut [usize]) -> Result<(), SasError>{
+        let len = self.len;
+        if let Err(er) = self.push_cells(plan, src, text, errors){
+            for col in self.columns.iter_mut(){
+                col.truncate(len);
+            }
+            return Err(er);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn push_cells(&mut self, plan : &[ColPlan], src : &[u8], text : &TextOpts, errors : &mut [usize]) -> Result<(), SasError>{
+        for (col, &j) in self.columns.iter_mut().zip(self.cols.iter()){
+            let plan = match plan.get(j){
+                Some(val) => val,
+                None => {
+                    col.push_missing();
+                    continue;
+                }
+            };
+            let bytes = match src.get(plan.off..plan.off + plan.len){
+                Some(val) => val,
+                None => return Err(SasError::BufLen),
+            };
+            match (col, plan.op){
+                (BatchColumn::Numeric{values, validity}, CellOp::Num(num)) => {
+                    let x = read_num(num, bytes);
+                    values.push(x);
+                    validity.push(!x.is_nan());
+                }
+                (BatchColumn::Date{days, validity}, CellOp::Date(num)) => {
+                    let x = read_num(num, bytes);
+                    let val = unix_days(x);
+                    days.push(val.unwrap_or(0));
+                    validity.push(val.is_some());
+                }
+                (BatchColumn::DateTime{micros, validity}, CellOp::DateTime(num)) => {
+                    let x = read_num(num, bytes);
+                    let val = to_micros(x - EPOCH_SECS);
+                    micros.push(val.unwrap_or(0));
+                    validity.push(val.is_some());
+                }
+                (BatchColumn::Time{micros, validity}, CellOp::Time(num)) => {
+                    let x = read_num(num, bytes);
+                    let val = to_micros(x);
+                    micros.push(val.unwrap_or(0));
+                    validity.push(val.is_some());
+                }
+                (BatchColumn::Text{offsets, bytes : arena}, CellOp::Text) => {
+                    text.push(bytes, arena, &mut errors[j])?;
+                    offsets.push(arena.len());
+                }
+                _ => return Err(SasError::SasProperty("batch column does not match the decode plan".to_string())),
+            }
+        }
+        Ok(())
+    }
+}
+
+//Days from 1970-01-01, None for missing values and dates out of the i32
+//range.
+fn unix_days(x : f64) -> Option<i32>{
+    if x.is_nan(){
+        return None;
+    }
+    i32::try_from((x as i64).checked_sub(i64::from(EPOCH_DAYS))?).ok()
+}
+
+//Microseconds, None for missing values and values out of the i64 range.
+fn to_micros(secs : f64) -> Option<i64>{
+    let x = (secs * 1e6).round();
+    (x >= i64::MIN as f64 && x < i64::MAX as f64).then_some(x as i64)
+}
+
+//How character cells are turned into the bytes of a text column.
+pub(crate) struct TextOpts{
+    pub(crate) decoder : Encoding,
+    pub(crate) policy : DecodePolicy,
+    pub(crate) trim : bool,
+}
+
+impl TextOpts{
+    fn push(&self, bytes : &[u8], arena : &mut Vec<u8>, errors : &mut usize) -> Result<(), SasError>{
+        if self.policy == DecodePolicy::Raw{
+            arena.extend_from_slice(bytes);
+            return Ok(());
+        }
+        //ASCII text needs no decoding in most encodings
+        if self.decoder.ascii_compatible(){
+            let mut end = bytes.len();
+            if self.trim{
+                end = bytes.iter().rposition(|x| *x != 0 && *x != b' ').map_or(0, |i| i + 1);
+            }
+            if bytes[..end].is_ascii(){
+                arena.extend_from_slice(&bytes[..end]);
+                return Ok(());
+            }
+        }
+        let st = decode_text(&self.decoder, self.policy, errors, bytes)?;
+        if self.trim{
+            arena.extend_from_slice(st.trim_end_matches(['\u{0000}', '\u{0020}']).as_bytes());
+        } else {
+            arena.extend_from_slice(st.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl<R : Read> SAS7bdat<R>{
+    /// Reads up to `n` rows into typed column buffers, decoded straight
+    /// from the page bytes without a `SasVal` per cell. The batch is shorter
+    /// than `n` at the end of the file, and empty after it.
+    pub fn read_batch(&mut self, n : usize) -> Result<ColumnBatch, SasError>{
+        let cols : Vec<usize> = (0..self.props.col_cnt).collect();
+        self.read_batch_columns(n, &cols)
+    }
+
+    /// Like `read_batch`, but only decodes the columns with the indices in
+    /// `cols`, in that order.
+    ///
+    /// If a row cannot be read, the rows read before it are returned as a
+    /// shorter batch and the error by the next call.
+    pub fn read_batch_columns(&mut self, n : usize, cols : &[usize]) -> Result<ColumnBatch, SasError>{
+        if let Some(er) = self.batch_err.take(){
+            return Err(er);
+        }
+        if let Some(j) = cols.iter().find(|j| **j >= self.props.col_cnt){
+            return Err(SasError::SasProperty(format!("no column {j}, the file has {}", self.props.col_cnt)));
+        }
+        let cap = n.min(self.row_count);
+        let columns = cols.iter().map(|j| BatchColumn::new(self.plan.get(*j).map(|x| x.op), cap)).collect();
+        self.batch = Some(ColumnBatch{columns, cols : cols.to_vec(), len : 0});
+        let mut res = Ok(());
+        while self.batch.as_ref().is_some_and(|x| x.len < n){
+            match self.read_line(){
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(er) => {
+                    res = Err(er);
+                    break;
+                }
+            }
+        }
+        let batch = self.batch.take().ok_or(SasError::Read)?;
+        match res{
+            Err(er) if batch.is_empty() => Err(er),
+            Err(er) => {
+                self.batch_err = Some(er);
+                Ok(batch)
+            }
+            Ok(()) => Ok(batch),
+        }
+    }
+}
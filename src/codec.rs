@@ -0,0 +1,174 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Chain, Cursor, Read};
+use std::path::Path;
+use crate::{PrefetchReader, ReadOptions, SAS7bdat, SasError};
+
+/// Compression wrapped around a sas7bdat file, detected by its magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec{
+    Plain,
+    Gzip,
+    Zstd,
+    Xz,
+    Zip,
+}
+
+const GZIP_MAGIC : &[u8] = b"\x1f\x8b";
+const ZSTD_MAGIC : &[u8] = b"\x28\xb5\x2f\xfd";
+const XZ_MAGIC : &[u8] = b"\xfd7zXZ\x00";
+const ZIP_MAGIC : &[u8] = b"PK\x03\x04";
+
+impl Codec{
+    pub fn detect(magic : &[u8]) -> Codec{
+        if magic.starts_with(GZIP_MAGIC){
+            Codec::Gzip
+        } else if magic.starts_with(ZSTD_MAGIC){
+            Codec::Zstd
+        } else if magic.starts_with(XZ_MAGIC){
+            Codec::Xz
+        } else if magic.starts_with(ZIP_MAGIC){
+            Codec::Zip
+        } else {
+            Codec::Plain
+        }
+    }
+
+    /// Name of the codec, which is also the name of the cargo feature
+    /// enabling it.
+    pub fn name(&self) -> &'static str{
+        match self{
+            Codec::Plain => "plain",
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+            Codec::Zip => "zip",
+        }
+    }
+}
+
+//Plain data is handed back without the buffer used to detect the codec, as
+//SAS7bdat buffers its input itself.
+fn wrap<R : Read + Send + 'static>(codec : Codec, rdr : BufReader<R>) -> Result<Box<dyn Read + Send>, SasError>{
+    match codec{
+        Codec::Plain => Ok(Box::new(unbuffered(rdr))),
+        #[cfg(feature = "gzip")]
+        Codec::Gzip => Ok(Box::new(flate2::bufread::MultiGzDecoder::new(rdr))),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd => match zstd::stream::read::Decoder::with_buffer(rdr){
+            Ok(val) => Ok(Box::new(val)),
+            Err(er) => Err(SasError::Io(er)),
+        }
+        #[cfg(feature = "xz")]
+        Codec::Xz => Ok(Box::new(xz2::bufread::XzDecoder::new_multi_decoder(rdr))),
+        Codec::Zip => Err(SasError::Codec("zip archives can only be opened from a file".to_string())),
+        #[allow(unreachable_patterns)]
+        _ => Err(SasError::Codec(format!("{} input requires the `{}` feature", codec.name(), codec.name()))),
+    }
+}
+
+//the bytes read by fill_buf in front of the rest of the reader
+fn unbuffered<R : Read>(rdr : BufReader<R>) -> Chain<Cursor<Vec<u8>>, R>{
+    let head = rdr.buffer().to_vec();
+    Cursor::new(head).chain(rdr.into_inner())
+}
+
+/// Wraps `reader` into the decompressor matching its first bytes. Plain
+/// sas7bdat data is passed through unchanged.
+pub fn decompress<R : Read + Send + 'static>(reader : R) -> Result<Box<dyn Read + Send>, SasError>{
+    let mut rdr = BufReader::new(reader);
+    let codec = match rdr.fill_buf(){
+        Ok(magic) => Codec::detect(magic),
+        Err(er) => return Err(SasError::Io(er)),
+    };
+    wrap(codec, rdr)
+}
+
+/// Opens the sas7bdat data at `path`, or in its zip member `member`, as a
+/// plain reader, see `SAS7bdat::open`. With `prefetch` above 0 the reader
+/// reads up to `prefetch` pages ahead on a background thread, see
+/// `PrefetchReader`.
+///
+/// ```no_run
+/// # use sas::{open_reader, ReadOptions};
+/// let mut sas = ReadOptions::default().read(open_reader("/mnt/nfs/data.sas7bdat", None, 8).unwrap()).unwrap();
+/// ```
+pub fn open_reader<P : AsRef<Path>>(path : P, member : Option<&str>, prefetch : usize) -> Result<Box<dyn Read + Send>, SasError>{
+    open(path.as_ref(), member, prefetch)
+}
+
+fn open(path : &Path, member : Option<&str>, prefetch : usize) -> Result<Box<dyn Read + Send>, SasError>{
+    let mut rdr = BufReader::new(File::open(path).map_err(SasError::Io)?);
+    let codec = match rdr.fill_buf(){
+        Ok(magic) => Codec::detect(magic),
+        Err(er) => return Err(SasError::Io(er)),
+    };
+    match (codec, member){
+        (Codec::Zip, _) => open_zip_member(path, member, prefetch),
+        (_, Some(_)) => Err(SasError::Codec(format!("{} is not a zip archive", path.display()))),
+        (_, None) if prefetch > 0 => Ok(Box::new(PrefetchReader::new(wrap(codec, rdr)?, prefetch))),
+        (_, None) => wrap(codec, rdr),
+    }
+}
+
+#[cfg(feature = "zip")]
+fn open_zip_member(path : &Path, member : Option<&str>, prefetch : usize) -> Result<Box<dyn Read + Send>, SasError>{
+    let zip_err = |er : zip::result::ZipError| SasError::Codec(er.to_string());
+    let mut archive = zip::ZipArchive::new(File::open(path).map_err(SasError::Io)?).map_err(zip_err)?;
+    let idx = match member{
+        Some(name) => match archive.index_for_name(name){
+            Some(idx) => idx,
+            None => return Err(SasError::Codec(format!("no member {name} in {}", path.display()))),
+        }
+        None => {
+            let candidates : Vec<usize> = (0..archive.len())
+                .filter(|i| archive.name_for_index(*i)
+                        .is_some_and(|name| name.to_lowercase().ends_with(".sas7bdat")))
+                .collect();
+            match candidates[..]{
+                [idx] => idx,
+                [] => return Err(SasError::Codec(format!("no sas7bdat member in {}", path.display()))),
+                _ => return Err(SasError::Codec(format!("several sas7bdat members in {}, pick one by name", path.display()))),
+            }
+        }
+    };
+
+    //fails early on members that cannot be read, e.g. with an unsupported
+    //compression method
+    archive.by_index(idx).map_err(zip_err)?;
+    //The member borrows from the archive, so it is read on a thread owning
+    //the archive. The zip reader checks the CRC at the end of the member.
+    Ok(Box::new(PrefetchReader::spawn(prefetch, move |pump| match archive.by_index(idx){
+        Ok(member) => pump.run(member),
+        Err(er) => pump.fail(std::io::Error::other(er)),
+    })))
+}
+
+#[cfg(not(feature = "zip"))]
+fn open_zip_member(_path : &Path, _member : Option<&str>, _prefetch : usize) -> Result<Box<dyn Read + Send>, SasError>{
+    Err(SasError::Codec("zip input requires the `zip` feature".to_string()))
+}
+
+impl SAS7bdat<Box<dyn Read + Send>>{
+    /// Opens a sas7bdat file, decompressing gzip, zstd and xz files on the fly
+    /// and reading the only sas7bdat member of a zip archive.
+    pub fn open<P : AsRef<Path>>(path : P) -> Result<Self, SasError>{
+        ReadOptions::default().open(path)
+    }
+
+    /// Opens the member `member` of the zip archive at `path`.
+    pub fn open_member<P : AsRef<Path>>(path : P, member : &str) -> Result<Self, SasError>{
+        ReadOptions::default().open_member(path, member)
+    }
+}
+
+impl ReadOptions{
+    /// Opens a file, see `SAS7bdat::open`.
+    pub fn open<P : AsRef<Path>>(&self, path : P) -> Result<SAS7bdat<Box<dyn Read + Send>>, SasError>{
+        self.read(open(path.as_ref(), None, 0)?)
+    }
+
+    /// Opens a member of a zip archive, see `SAS7bdat::open_member`.
+    pub fn open_member<P : AsRef<Path>>(&self, path : P, member : &str) -> Result<SAS7bdat<Box<dyn Read + Send>>, SasError>{
+        self.read(open(path.as_ref(), Some(member), 0)?)
+    }
+}
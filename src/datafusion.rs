@@ -0,0 +1,388 @@
+//! DataFusion file format, with the `datafusion` feature.
+//!
+//! ```no_run
+//! # async fn run() -> datafusion::error::Result<()> {
+//! use datafusion::prelude::SessionContext;
+//! use sas::ReadOptions;
+//!
+//! let ctx = SessionContext::new().enable_url_table();
+//! sas::datafusion::register_format(&ctx, ReadOptions::default())?;
+//! ctx.sql("SELECT a.id, b.visit FROM 'data/*.sas7bdat' a JOIN 'visits.sas7bdat' b ON a.id = b.id").await?.show().await?;
+//!
+//! sas::datafusion::register_sas7bdat(&ctx, "extract", "data/", ReadOptions::default()).await?;
+//! ctx.sql("SELECT count(*) FROM extract").await?.show().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The schema comes from the column metadata: numerics are `Float64`,
+//! character columns `Utf8` (`Binary` with `DecodePolicy::Raw`), dates
+//! `Date32`, datetimes `Timestamp` and times `Duration`, in microseconds.
+//! Labels and formats are kept in the field metadata under `label` and
+//! `format`. Only the selected columns are decoded, and the reader stops
+//! once a limit is reached. Files are fetched by ranges of pages, and read
+//! on tokio's blocking threads.
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use ::datafusion::arrow::array::{new_null_array, ArrayRef, BinaryArray, Date32Array, DurationMicrosecondArray, Float64Array, RecordBatch, RecordBatchOptions, StringArray, TimestampMicrosecondArray};
+use ::datafusion::arrow::buffer::{BooleanBuffer, Buffer, NullBuffer, OffsetBuffer, ScalarBuffer};
+use ::datafusion::arrow::datatypes::{Field, Schema, SchemaRef};
+use ::datafusion::catalog::Session;
+use ::datafusion::common::tree_node::TreeNodeRecursion;
+use ::datafusion::common::{not_impl_err, DataFusionError, GetExt, Result, Statistics};
+use ::datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use ::datafusion::datasource::file_format::{FileFormat, FileFormatFactory};
+use ::datafusion::datasource::listing::{ListingOptions, PartitionedFile};
+use ::datafusion::datasource::physical_plan::{FileOpenFuture, FileOpener, FileScanConfig, FileSource};
+use ::datafusion::datasource::projection::{ProjectionOpener, SplitProjection};
+use ::datafusion::datasource::source::DataSourceExec;
+use ::datafusion::datasource::table_schema::TableSchema;
+use ::datafusion::object_store::path::Path;
+use ::datafusion::object_store::{ObjectMeta, ObjectStore, ObjectStoreExt};
+use ::datafusion::physical_expr::PhysicalExpr;
+use ::datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+use ::datafusion::physical_plan::projection::ProjectionExprs;
+use ::datafusion::physical_plan::ExecutionPlan;
+use ::datafusion::prelude::SessionContext;
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use tokio::runtime::Handle;
+use crate::{BatchColumn, ColumnBatch, DecodePolicy, RangeReader, RangeSource, ReadOptions, SAS7bdat, SasError, Validity};
+
+const EXTENSION : &str = "sas7bdat";
+
+/// Adds the sas7bdat format to the session, so that tables can be created
+/// from files ending in `.sas7bdat`, e.g. `SELECT * FROM 'data/*.sas7bdat'`
+/// once url tables are enabled.
+pub fn register_format(ctx : &SessionContext, options : ReadOptions) -> Result<()>{
+    let factory = Arc::new(SasFormatFactory{options});
+    ctx.state_ref().write().register_file_format(factory, true)?;
+    Ok(())
+}
+
+/// Registers the sas7bdat files under `table_path`, a file, a directory or a
+/// glob, as one table.
+pub async fn register_sas7bdat(ctx : &SessionContext, name : &str, table_path : &str, options : ReadOptions) -> Result<()>{
+    let listing = ListingOptions::new(Arc::new(SasFormat{options})).with_file_extension(format!(".{EXTENSION}"));
+    ctx.register_listing_table(name, table_path, listing, None, None).await
+}
+
+/// Creates `SasFormat`s for the session, see `register_format`.
+#[derive(Debug, Default)]
+pub struct SasFormatFactory{
+    pub options : ReadOptions,
+}
+
+impl FileFormatFactory for SasFormatFactory{
+    fn create(&self, _state : &dyn Session, _format_options : &HashMap<String, String>) -> Result<Arc<dyn FileFormat>>{
+        Ok(self.default())
+    }
+
+    fn default(&self) -> Arc<dyn FileFormat>{
+        Arc::new(SasFormat{options : self.options.clone()})
+    }
+}
+
+impl GetExt for SasFormatFactory{
+    fn get_ext(&self) -> String{
+        EXTENSION.to_string()
+    }
+}
+
+/// The sas7bdat `FileFormat`, for a `ListingTable` over one or more files.
+#[derive(Debug, Default)]
+pub struct SasFormat{
+    pub options : ReadOptions,
+}
+
+#[async_trait]
+impl FileFormat for SasFormat{
+    fn get_ext(&self) -> String{
+        EXTENSION.to_string()
+    }
+
+    fn get_ext_with_compression(&self, compression : &FileCompressionType) -> Result<String>{
+        if compression.is_compressed(){
+            return not_impl_err!("compressed sas7bdat files are not supported in tables");
+        }
+        Ok(EXTENSION.to_string())
+    }
+
+    fn compression_type(&self) -> Option<FileCompressionType>{
+        None
+    }
+
+    //Columns that are in several files take their labels from the first
+    //file, their types must agree.
+    async fn infer_schema(&self, _state : &dyn Session, store : &Arc<dyn ObjectStore>, objects : &[ObjectMeta]) -> Result<SchemaRef>{
+        let mut schemas = Vec::new();
+        for object in objects{
+            let (store, object, options) = (Arc::clone(store), object.clone(), self.options.clone());
+            schemas.push(blocking(move || file_schema(open(store, object, &options)?, options.decode_policy)).await?);
+        }
+        let first = match schemas.first(){
+            Some(val) => val.clone(),
+            None => return Ok(Arc::new(Schema::empty())),
+        };
+        let plain = schemas.iter().map(|x| Schema::new(x.fields().iter().map(|f| f.as_ref().clone().with_metadata(HashMap::new())).collect::<Vec<Field>>()));
+        let merged = Schema::try_merge(plain)?;
+        let fields : Vec<Field> = merged.fields().iter().map(|f| match first.field_with_name(f.name()){
+            Ok(val) => f.as_ref().clone().with_metadata(val.metadata().clone()),
+            Err(_) => f.as_ref().clone(),
+        }).collect();
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    async fn infer_stats(&self, _state : &dyn Session, _store : &Arc<dyn ObjectStore>, table_schema : SchemaRef, _object : &ObjectMeta) -> Result<Statistics>{
+        Ok(Statistics::new_unknown(&table_schema))
+    }
+
+    async fn create_physical_plan(&self, _state : &dyn Session, conf : FileScanConfig) -> Result<Arc<dyn ExecutionPlan>>{
+        Ok(DataSourceExec::from_data_source(conf))
+    }
+
+    fn file_source(&self, table_schema : TableSchema) -> Arc<dyn FileSource>{
+        Arc::new(SasSource{
+            projection : SplitProjection::unprojected(&table_schema),
+            table_schema,
+            options : self.options.clone(),
+            batch_size : None,
+            metrics : ExecutionPlanMetricsSet::new(),
+        })
+    }
+}
+
+/// Reads the selected columns of sas7bdat files in record batches.
+#[derive(Clone)]
+pub struct SasSource{
+    table_schema : TableSchema,
+    options : ReadOptions,
+    projection : SplitProjection,
+    batch_size : Option<usize>,
+    metrics : ExecutionPlanMetricsSet,
+}
+
+impl FileSource for SasSource{
+    fn create_file_opener(&self, store : Arc<dyn ObjectStore>, config : &FileScanConfig, _partition : usize) -> Result<Arc<dyn FileOpener>>{
+        let file_schema = self.table_schema.file_schema();
+        let batch_size = self.batch_size.unwrap_or(8192);
+        let opener = Arc::new(SasOpener{
+            store,
+            options : self.options.clone(),
+            schema : Arc::new(file_schema.project(&self.projection.file_indices)?),
+            batch_size : config.limit.map_or(batch_size, |x| x.clamp(1, batch_size)),
+        });
+        ProjectionOpener::try_new(self.projection.clone(), opener, file_schema)
+    }
+
+    fn table_schema(&self) -> &TableSchema{
+        &self.table_schema
+    }
+
+    fn with_batch_size(&self, batch_size : usize) -> Arc<dyn FileSource>{
+        Arc::new(SasSource{batch_size : Some(batch_size), ..self.clone()})
+    }
+
+    fn try_pushdown_projection(&self, projection : &ProjectionExprs) -> Result<Option<Arc<dyn FileSource>>>{
+        let merged = self.projection.source.try_merge(projection)?;
+        let projection = SplitProjection::new(self.table_schema.file_schema(), &merged);
+        Ok(Some(Arc::new(SasSource{projection, ..self.clone()})))
+    }
+
+    fn projection(&self) -> Option<&ProjectionExprs>{
+        Some(&self.projection.source)
+    }
+
+    fn metrics(&self) -> &ExecutionPlanMetricsSet{
+        &self.metrics
+    }
+
+    fn file_type(&self) -> &str{
+        EXTENSION
+    }
+
+    //rows can only be found by reading the file from the start
+    fn supports_repartitioning(&self) -> bool{
+        false
+    }
+
+    fn apply_expressions(&self, f : &mut dyn FnMut(&Arc<dyn PhysicalExpr>) -> Result<TreeNodeRecursion>) -> Result<TreeNodeRecursion>{
+        ::datafusion::physical_plan::apply_expression_roots(self.projection.source.iter(), f)
+    }
+}
+
+struct SasOpener{
+    store : Arc<dyn ObjectStore>,
+    options : ReadOptions,
+    //the columns to read
+    schema : SchemaRef,
+    batch_size : usize,
+}
+
+impl FileOpener for SasOpener{
+    fn open(&self, file : PartitionedFile) -> Result<FileOpenFuture>{
+        if file.range.is_some(){
+            return not_impl_err!("sas7bdat files cannot be read by byte ranges");
+        }
+        let store = Arc::clone(&self.store);
+        let options = self.options.clone();
+        let schema = Arc::clone(&self.schema);
+        let batch_size = self.batch_size;
+        Ok(Box::pin(async move{
+            let policy = options.decode_policy;
+            let sas = blocking(move || open(store, file.object_meta, &options)).await?;
+            let batches = Batches::new(sas, schema, policy, batch_size);
+            //each batch is read on a blocking thread
+            Ok(stream::unfold(Some(batches), |state| async move{
+                let mut batches = state?;
+                match blocking(move || Ok((batches.next(), batches))).await{
+                    Ok((Some(res), batches)) => Some((res, Some(batches))),
+                    Ok((None, _)) => None,
+                    Err(er) => Some((Err(er), None)),
+                }
+            }).boxed())
+        }))
+    }
+}
+
+//Record batches of the columns in schema, columns missing from the file are
+//null.
+struct Batches{
+    sas : SAS7bdat<RangeReader<StoreRange>>,
+    schema : SchemaRef,
+    //index of each column in the file
+    cols : Vec<Option<usize>>,
+    policy : DecodePolicy,
+    batch_size : usize,
+    done : bool,
+}
+
+impl Batches{
+    fn new(sas : SAS7bdat<RangeReader<StoreRange>>, schema : SchemaRef, policy : DecodePolicy, batch_size : usize) -> Batches{
+        let cols = schema.fields().iter().map(|f| sas.col_names().iter().position(|x| x == f.name())).collect();
+        Batches{sas, schema, cols, policy, batch_size, done : false}
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>>{
+        let read : Vec<usize> = self.cols.iter().flatten().copied().collect();
+        let batch = self.sas.read_batch_columns(self.batch_size, &read).map_err(to_datafusion)?;
+        if batch.is_empty(){
+            return Ok(None);
+        }
+        let len = batch.len();
+        let mut arrays = to_arrays(batch, self.policy)?.into_iter();
+        let mut columns = Vec::with_capacity(self.cols.len());
+        for (col, field) in self.cols.iter().zip(self.schema.fields()){
+            let array = match col{
+                Some(_) => arrays.next(),
+                None => None,
+            };
+            columns.push(array.unwrap_or_else(|| new_null_array(field.data_type(), len)));
+        }
+        //count(*) reads no columns, only rows
+        let options = RecordBatchOptions::new().with_row_count(Some(len));
+        Ok(Some(RecordBatch::try_new_with_options(Arc::clone(&self.schema), columns, &options)?))
+    }
+}
+
+impl Iterator for Batches{
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Result<RecordBatch>>{
+        if self.done{
+            return None;
+        }
+        let res = self.next_batch().transpose();
+        self.done = !matches!(res, Some(Ok(_)));
+        res
+    }
+}
+
+//An object of a store as a RangeSource, for the blocking threads of the
+//runtime.
+struct StoreRange{
+    store : Arc<dyn ObjectStore>,
+    location : Path,
+    size : u64,
+    runtime : Handle,
+}
+
+impl RangeSource for StoreRange{
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>{
+        let end = self.size.min(offset.saturating_add(buf.len() as u64));
+        if offset >= end{
+            return Ok(0);
+        }
+        let data = self.runtime.block_on(self.store.get_range(&self.location, offset..end)).map_err(io::Error::other)?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+//Opens an object, fetching the header and the metadata pages. Runs on a
+//blocking thread.
+fn open(store : Arc<dyn ObjectStore>, object : ObjectMeta, options : &ReadOptions) -> Result<SAS7bdat<RangeReader<StoreRange>>>{
+    let src = StoreRange{store, location : object.location, size : object.size, runtime : Handle::current()};
+    options.read(RangeReader::new(src)).map_err(to_datafusion)
+}
+
+//Runs `f` on the blocking threads of the runtime, as the reader does
+//synchronous I/O.
+async fn blocking<T : Send + 'static, F : FnOnce() -> Result<T> + Send + 'static>(f : F) -> Result<T>{
+    match tokio::task::spawn_blocking(f).await{
+        Ok(val) => val,
+        Err(er) => Err(DataFusionError::External(Box::new(er))),
+    }
+}
+
+fn file_schema<R : io::Read>(mut sas : SAS7bdat<R>, policy : DecodePolicy) -> Result<Schema>{
+    //an empty batch has the types of the columns
+    let empty = sas.read_batch(0).map_err(to_datafusion)?;
+    let arrays = to_arrays(empty, policy)?;
+    let mut fields = Vec::with_capacity(arrays.len());
+    for (j, array) in arrays.iter().enumerate(){
+        let mut metadata = HashMap::new();
+        if !sas.col_label()[j].is_empty(){
+            metadata.insert("label".to_string(), sas.col_label()[j].clone());
+        }
+        if !sas.col_formats()[j].is_empty(){
+            metadata.insert("format".to_string(), sas.col_formats()[j].clone());
+        }
+        fields.push(Field::new(sas.col_names()[j].clone(), array.data_type().clone(), true).with_metadata(metadata));
+    }
+    Ok(Schema::new(fields))
+}
+
+fn to_datafusion(er : SasError) -> DataFusionError{
+    DataFusionError::Execution(format!("{er:?}"))
+}
+
+fn to_arrays(batch : ColumnBatch, policy : DecodePolicy) -> Result<Vec<ArrayRef>>{
+    batch.columns.into_iter().map(|col| to_array(col, policy)).collect()
+}
+
+fn to_array(col : BatchColumn, policy : DecodePolicy) -> Result<ArrayRef>{
+    let array : ArrayRef = match col{
+        BatchColumn::Numeric{values, validity} => Arc::new(Float64Array::new(values.into(), nulls(&validity))),
+        BatchColumn::Date{days, validity} => Arc::new(Date32Array::new(days.into(), nulls(&validity))),
+        BatchColumn::DateTime{micros, validity} => Arc::new(TimestampMicrosecondArray::new(micros.into(), nulls(&validity))),
+        BatchColumn::Time{micros, validity} => Arc::new(DurationMicrosecondArray::new(micros.into(), nulls(&validity))),
+        BatchColumn::Text{offsets, bytes} => {
+            let offsets = offsets.into_iter().map(i32::try_from).collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| DataFusionError::Execution("text of a batch exceeds 2 GiB, use a smaller batch size".to_string()))?;
+            let offsets = OffsetBuffer::new(ScalarBuffer::from(offsets));
+            match policy{
+                DecodePolicy::Raw => Arc::new(BinaryArray::try_new(offsets, Buffer::from_vec(bytes), None)?),
+                _ => Arc::new(StringArray::try_new(offsets, Buffer::from_vec(bytes), None)?),
+            }
+        }
+    };
+    Ok(array)
+}
+
+fn nulls(validity : &Validity) -> Option<NullBuffer>{
+    let bits = BooleanBuffer::new(Buffer::from_vec(validity.bits().to_vec()), 0, validity.len());
+    Some(NullBuffer::new(bits))
+}
@@ -0,0 +1,330 @@
+use std::fmt;
+use encoding_rs::*;
+use crate::SasError;
+use crate::codepages::*;
+
+/// Text encoding of a sas7bdat file, named as in SAS.
+#[derive(Clone, Copy)]
+pub struct Encoding{
+    name : &'static str,
+    kind : Kind,
+}
+
+/// How character cells that are not valid in the file encoding are read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodePolicy{
+    /// Fail the read with `SasError::ByteConversion`.
+    #[default]
+    Strict,
+    /// Replace malformed bytes with U+FFFD.
+    Replace,
+    /// Drop a multi-byte character cut off at the end of the cell, other
+    /// malformed bytes fail the read.
+    TrimIncomplete,
+    /// Return the bytes of the cell undecoded as `SasVal::Bytes`.
+    Raw,
+}
+
+#[derive(Clone, Copy)]
+enum Kind{
+    Web(&'static encoding_rs::Encoding),
+    Latin1,
+    Table(&'static [u16; 256]),
+    //known to SAS, but no decoder available
+    Unsupported,
+}
+
+fn id_entry(id : u8) -> Option<(&'static str, Kind)>{
+    use Kind::*;
+    let entry = match id{
+        20 => ("utf-8", Web(UTF_8)),
+        28 => ("us-ascii", Table(&US_ASCII)),
+        29 => ("latin1", Latin1),
+        30 => ("latin2", Web(ISO_8859_2)),
+        31 => ("latin3", Web(ISO_8859_3)),
+        32 => ("latin4", Web(ISO_8859_4)),
+        33 => ("cyrillic", Web(ISO_8859_5)),
+        34 => ("arabic", Web(ISO_8859_6)),
+        35 => ("greek", Web(ISO_8859_7)),
+        36 => ("hebrew", Web(ISO_8859_8)),
+        37 => ("latin5", Table(&ISO_8859_9)),
+        38 => ("latin6", Web(ISO_8859_10)),
+        39 => ("thai", Table(&ISO_8859_11)),
+        40 => ("latin9", Web(ISO_8859_15)),
+        41 => ("pcoem437", Table(&CP437)),
+        42 => ("pcoem850", Table(&CP850)),
+        43 => ("pcoem852", Table(&CP852)),
+        44 => ("pcoem857", Table(&CP857)),
+        45 => ("pcoem858", Table(&CP858)),
+        46 => ("pcoem862", Table(&CP862)),
+        47 => ("pcoem864", Table(&CP864)),
+        48 => ("pcoem865", Table(&CP865)),
+        49 => ("pcoem866", Web(IBM866)),
+        50 => ("pcoem869", Table(&CP869)),
+        51 => ("pcoem874", Web(WINDOWS_874)),
+        52 => ("pcoem921", Table(&CP921)),
+        53 => ("pcoem922", Table(&CP922)),
+        54 => ("pcoem1129", Table(&CP1129)),
+        55 => ("pcoem720", Table(&CP720)),
+        56 => ("pcoem737", Table(&CP737)),
+        57 => ("pcoem775", Table(&CP775)),
+        58 => ("pcoem860", Table(&CP860)),
+        59 => ("pcoem863", Table(&CP863)),
+        60 => ("wlatin2", Web(WINDOWS_1250)),
+        61 => ("wcyrillic", Web(WINDOWS_1251)),
+        62 => ("wlatin1", Web(WINDOWS_1252)),
+        63 => ("wgreek", Web(WINDOWS_1253)),
+        64 => ("wturkish", Web(WINDOWS_1254)),
+        65 => ("whebrew", Web(WINDOWS_1255)),
+        66 => ("warabic", Web(WINDOWS_1256)),
+        67 => ("wbaltic", Web(WINDOWS_1257)),
+        68 => ("wvietnamese", Web(WINDOWS_1258)),
+        69 => ("macroman", Web(MACINTOSH)),
+        70 => ("macarabic", Table(&MAC_ARABIC)),
+        71 => ("machebrew", Unsupported),
+        72 => ("macgreek", Table(&MAC_GREEK)),
+        73 => ("macthai", Unsupported),
+        75 => ("macturkish", Table(&MAC_TURKISH)),
+        76 => ("macukraine", Web(X_MAC_CYRILLIC)),
+        118 => ("ms-950", Web(BIG5)),
+        119 => ("euc-tw", Unsupported),
+        123 => ("big5", Web(BIG5)),
+        125 => ("euc-cn", Web(GB18030)),
+        126 => ("ms-936", Web(GBK)),
+        128 => ("zpce", Unsupported),
+        134 => ("euc-jp", Web(EUC_JP)),
+        136 => ("ms-949", Web(EUC_KR)),
+        137 => ("jpce", Unsupported),
+        138 => ("shift-jis", Web(SHIFT_JIS)),
+        140 => ("euc-kr", Web(EUC_KR)),
+        141 => ("kpce", Web(EUC_KR)),
+        142 => ("kwin", Web(EUC_KR)),
+        163 => ("maciceland", Table(&MAC_ICELAND)),
+        167 => ("iso-2022-jp", Web(ISO_2022_JP)),
+        168 => ("iso-2022-kr", Unsupported),
+        169 => ("iso-2022-cn", Unsupported),
+        172 => ("iso-2022-cn-ext", Unsupported),
+        205 => ("gb18030", Web(GB18030)),
+        227 => ("latin8", Web(ISO_8859_14)),
+        242 => ("latin7", Web(ISO_8859_13)),
+        245 => ("maccroatian", Table(&MAC_CROATIAN)),
+        246 => ("maccyrillic", Table(&MAC_CYRILLIC)),
+        247 => ("macromania", Table(&MAC_ROMANIAN)),
+        248 => ("shift_jisx0213", Unsupported),
+        _ => return None,
+    };
+    Some(entry)
+}
+
+//EBCDIC code pages have no known encoding id, they are only chosen by name.
+const EBCDIC : [(&str, &[u16; 256]); 22] = [
+    ("ebcdic037", &EBCDIC037),
+    ("ebcdic273", &EBCDIC273),
+    ("ebcdic277", &EBCDIC277),
+    ("ebcdic278", &EBCDIC278),
+    ("ebcdic280", &EBCDIC280),
+    ("ebcdic284", &EBCDIC284),
+    ("ebcdic285", &EBCDIC285),
+    ("ebcdic297", &EBCDIC297),
+    ("ebcdic500", &EBCDIC500),
+    ("ebcdic870", &EBCDIC870),
+    ("ebcdic871", &EBCDIC871),
+    ("ebcdic875", &EBCDIC875),
+    ("ebcdic1047", &EBCDIC1047),
+    ("ebcdic1140", &EBCDIC1140),
+    ("ebcdic1141", &EBCDIC1141),
+    ("ebcdic1142", &EBCDIC1142),
+    ("ebcdic1143", &EBCDIC1143),
+    ("ebcdic1144", &EBCDIC1144),
+    ("ebcdic1145", &EBCDIC1145),
+    ("ebcdic1146", &EBCDIC1146),
+    ("ebcdic1147", &EBCDIC1147),
+    ("ebcdic1148", &EBCDIC1148),
+];
+
+impl Encoding{
+    pub const UTF_8 : Encoding = Encoding{name : "utf-8", kind : Kind::Web(&UTF_8_INIT)};
+    pub const WLATIN1 : Encoding = Encoding{name : "wlatin1", kind : Kind::Web(&WINDOWS_1252_INIT)};
+
+    /// Encoding of the id stored in the file header. Returns `None` for
+    /// unknown ids and for 0 and 204, which stand for the session encoding
+    /// of the SAS writing the file.
+    pub fn from_id(id : u8) -> Option<Encoding>{
+        id_entry(id).map(|(name, kind)| Encoding{name, kind})
+    }
+
+    /// Looks up an encoding by its SAS name, e.g. `wlatin1` or `ebcdic1047`,
+    /// or by a WHATWG label such as `windows-1252`.
+    pub fn from_name(name : &str) -> Option<Encoding>{
+        let name = name.trim().to_ascii_lowercase();
+        if let Some(enc) = (1..=u8::MAX).filter_map(Encoding::from_id).find(|x| x.name == name){
+            return Some(enc);
+        }
+        if let Some((name, table)) = EBCDIC.iter().find(|x| x.0 == name){
+            return Some(Encoding{name, kind : Kind::Table(table)});
+        }
+        encoding_rs::Encoding::for_label(name.as_bytes())
+            .map(|x| Encoding{name : x.name(), kind : Kind::Web(x)})
+    }
+
+    pub fn name(&self) -> &'static str{
+        self.name
+    }
+
+    /// False for encodings known to SAS that cannot be decoded.
+    pub fn is_supported(&self) -> bool{
+        !matches!(self.kind, Kind::Unsupported)
+    }
+
+    //True if ASCII bytes decode to the same characters, which is not the
+    //case for EBCDIC.
+    pub(crate) fn ascii_compatible(&self) -> bool{
+        match self.kind{
+            Kind::Web(x) => x.is_ascii_compatible(),
+            Kind::Latin1 => true,
+            Kind::Table(_) | Kind::Unsupported => false,
+        }
+    }
+
+    pub(crate) fn decode(&self, bytes : &[u8]) -> Result<String, SasError>{
+        match self.kind{
+            Kind::Web(x) => match x.decode_without_bom_handling_and_without_replacement(bytes){
+                Some(val) => Ok(val.into_owned()),
+                None => Err(SasError::ByteConversion),
+            }
+            Kind::Latin1 => Ok(bytes.iter().map(|x| char::from(*x)).collect()),
+            Kind::Table(table) => {
+                let mut res = String::with_capacity(bytes.len());
+                for x in bytes{
+                    match char::from_u32(u32::from(table[usize::from(*x)])){
+                        Some(val) if val != char::REPLACEMENT_CHARACTER => res.push(val),
+                        _ => return Err(SasError::ByteConversion),
+                    }
+                }
+                Ok(res)
+            }
+            Kind::Unsupported => Err(SasError::UnknownDecoder),
+        }
+    }
+
+    //Decodes a cell according to policy. The flag is set if bytes had to be
+    //replaced or dropped.
+    pub(crate) fn decode_cell(&self, bytes : &[u8], policy : DecodePolicy) -> Result<(String, bool), SasError>{
+        let err = match self.decode(bytes){
+            Ok(val) => return Ok((val, false)),
+            Err(er) => er,
+        };
+        match (policy, self.kind){
+            (DecodePolicy::Replace, Kind::Web(x)) => {
+                Ok((x.decode_without_bom_handling(bytes).0.into_owned(), true))
+            }
+            (DecodePolicy::Replace, Kind::Table(table)) => {
+                let res = bytes.iter()
+                    .map(|x| char::from_u32(u32::from(table[usize::from(*x)])).unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect();
+                Ok((res, true))
+            }
+            (DecodePolicy::TrimIncomplete, Kind::Web(x)) => {
+                //Without the last flag the decoder keeps an incomplete trailing
+                //sequence pending instead of reporting it.
+                let mut decoder = x.new_decoder_without_bom_handling();
+                let cap = decoder.max_utf8_buffer_length_without_replacement(bytes.len()).unwrap_or(bytes.len() * 3);
+                let mut res = String::with_capacity(cap);
+                match decoder.decode_to_string_without_replacement(bytes, &mut res, false){
+                    (DecoderResult::InputEmpty, _) => Ok((res, true)),
+                    _ => Err(err),
+                }
+            }
+            _ => Err(err),
+        }
+    }
+}
+
+/// Most likely encoding of the character cells, see
+/// `SAS7bdat::detect_encoding`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Clone, Debug)]
+pub struct EncodingGuess{
+    pub encoding : Encoding,
+    /// Between 0 and 1, grows with the number of non-ASCII bytes sampled.
+    pub confidence : f64,
+    /// Number of sampled cells containing non-ASCII bytes.
+    pub samples : usize,
+}
+
+const LATIN1 : Encoding = Encoding{name : "latin1", kind : Kind::Latin1};
+
+//Share of the non-ASCII characters that look like text rather than control
+//characters or rare symbols.
+fn plausibility(enc : Encoding, cells : &[&[u8]]) -> f64{
+    let mut good = 0;
+    let mut total = 0;
+    for cell in cells{
+        let text = match enc.decode(cell){
+            Ok(val) => val,
+            Err(_) => return 0.0,
+        };
+        for c in text.chars().filter(|x| !x.is_ascii()){
+            total += 1;
+            if c.is_alphanumeric() || "€‚„…‘’“”•–—™¡¢£¥§©«®°±²³´µ¶·¹º»¿×÷".contains(c){
+                good += 1;
+            }
+        }
+    }
+    if total == 0{
+        return 0.0;
+    }
+    f64::from(good) / f64::from(total)
+}
+
+//Guesses the encoding of cells among UTF-8, wlatin1, latin1 and the encoding
+//of the header. Valid multi-byte UTF-8 is rare in other encodings, so it
+//wins whenever the whole sample is valid UTF-8.
+pub(crate) fn guess_encoding(cells : &[&[u8]], header : Encoding) -> EncodingGuess{
+    let cells : Vec<&[u8]> = cells.iter().copied().filter(|x| !x.is_ascii()).collect();
+    let evidence = |n : usize| 1.0 - 0.5f64.powi(n.min(64) as i32);
+    let high_bytes = cells.iter().flat_map(|x| x.iter()).filter(|x| !x.is_ascii()).count();
+    if cells.is_empty(){
+        return EncodingGuess{encoding : header, confidence : 0.0, samples : 0};
+    }
+    if cells.iter().all(|x| std::str::from_utf8(x).is_ok()){
+        let multi_byte = cells.iter().flat_map(|x| x.iter()).filter(|x| **x >= 0xC0).count();
+        return EncodingGuess{encoding : Encoding::UTF_8, confidence : evidence(multi_byte), samples : cells.len()};
+    }
+    let mut best = (header, plausibility(header, &cells));
+    for enc in [Encoding::WLATIN1, LATIN1]{
+        let score = plausibility(enc, &cells);
+        if score > best.1{
+            best = (enc, score);
+        }
+    }
+    EncodingGuess{encoding : best.0, confidence : best.1 * evidence(high_bytes), samples : cells.len()}
+}
+
+//Encodings decoding the same way are equal, whatever their name, so utf8
+//equals utf-8 and windows-1252 equals wlatin1.
+impl PartialEq for Encoding{
+    fn eq(&self, other : &Encoding) -> bool{
+        match (self.kind, other.kind){
+            (Kind::Web(x), Kind::Web(y)) => x == y,
+            (Kind::Latin1, Kind::Latin1) => true,
+            (Kind::Table(x), Kind::Table(y)) => std::ptr::eq(x, y),
+            (Kind::Unsupported, Kind::Unsupported) => self.name == other.name,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Encoding {}
+
+impl fmt::Debug for Encoding{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "Encoding({})", self.name)
+    }
+}
+
+impl fmt::Display for Encoding{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
+        f.write_str(self.name)
+    }
+}
@@ -0,0 +1,798 @@
+//! Builds sas7bdat files from a declarative description, for tests and for
+//! minimal reproductions of reader bugs without sharing real data.
+//!
+//! ```
+//! use sas::fixture::{Cell, Column, Compression, Fixture};
+//!
+//! let mut f = Fixture{compression : Compression::Rle, ..Fixture::default()};
+//! f.columns.push(Column::numeric("x"));
+//! f.columns.push(Column::character("name", 8).with_label("Name"));
+//! f.rows.push(vec![Cell::Num(1.5), Cell::Str("a".to_string())]);
+//! let mut sas = sas::SAS7bdat::from_reader(std::io::Cursor::new(f.to_bytes())).unwrap();
+//! assert!(sas.read_line().unwrap());
+//! ```
+use std::cmp;
+use crate::MAGIC;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Endianness{
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Compression{
+    #[default]
+    None,
+    /// SASYZCRL
+    Rle,
+    /// SASYZCR2
+    Rdc,
+}
+
+/// The pages the rows are written to. Compressed rows are always subheaders
+/// on meta pages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Layout{
+    /// Metadata on meta pages, rows on the data pages that follow (or on the
+    /// meta pages after the one with the metadata for compressed files).
+    #[default]
+    Data,
+    /// Rows start on the first page, behind the metadata: a mix page, or the
+    /// first meta page for compressed files.
+    Mix,
+}
+
+/// Type and length in bytes of a column. Numerics of 3 to 7 bytes hold the
+/// high order bytes of the double.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnKind{
+    Numeric(usize),
+    Character(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column{
+    pub name : String,
+    pub label : String,
+    /// Format name, width and decimals, e.g. DATE9.
+    pub format : String,
+    pub width : u16,
+    pub decimals : u16,
+    pub kind : ColumnKind,
+}
+
+impl Column{
+    /// An 8 byte numeric column.
+    pub fn numeric(name : &str) -> Column{
+        Column::new(name, ColumnKind::Numeric(8))
+    }
+
+    pub fn character(name : &str, len : usize) -> Column{
+        Column::new(name, ColumnKind::Character(len))
+    }
+
+    pub fn new(name : &str, kind : ColumnKind) -> Column{
+        Column{
+            name : name.to_string(),
+            label : String::new(),
+            format : String::new(),
+            width : 0,
+            decimals : 0,
+            kind,
+        }
+    }
+
+    pub fn with_label(mut self, label : &str) -> Column{
+        self.label = label.to_string();
+        self
+    }
+
+    pub fn with_format(mut self, format : &str, width : u16, decimals : u16) -> Column{
+        self.format = format.to_string();
+        self.width = width;
+        self.decimals = decimals;
+        self
+    }
+
+    fn len(&self) -> usize{
+        match self.kind{
+            ColumnKind::Numeric(x) | ColumnKind::Character(x) => x,
+        }
+    }
+}
+
+/// A value of a row. Strings are padded with blanks to the column length,
+/// `Bytes` are written as they are, e.g. text in another encoding or a
+/// numeric with a chosen bit pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cell{
+    Num(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+/// Description of a file, turned into its bytes by `to_bytes`.
+#[derive(Clone, Debug)]
+pub struct Fixture{
+    pub name : String,
+    pub columns : Vec<Column>,
+    pub rows : Vec<Vec<Cell>>,
+    /// Encoding id written to the header, 20 is utf-8.
+    pub encoding : u8,
+    pub endianness : Endianness,
+    /// 64 bit layout, with 8 byte integers and an 8192 byte header.
+    pub u64 : bool,
+    pub compression : Compression,
+    pub page_len : usize,
+    pub layout : Layout,
+    /// Indices of rows flagged as deleted.
+    pub deleted : Vec<usize>,
+    /// Column order written to a column-list subheader, 1-based column
+    /// numbers.
+    pub col_list : Option<Vec<i16>>,
+    /// Writes a subheader-counts subheader.
+    pub sub_hdr_counts : bool,
+}
+
+impl Default for Fixture{
+    fn default() -> Fixture{
+        Fixture{
+            name : "FIXTURE".to_string(),
+            columns : Vec::new(),
+            rows : Vec::new(),
+            encoding : 20,
+            endianness : Endianness::Little,
+            u64 : true,
+            compression : Compression::None,
+            page_len : 4096,
+            layout : Layout::Data,
+            deleted : Vec::new(),
+            col_list : None,
+            sub_hdr_counts : false,
+        }
+    }
+}
+
+struct Ctx{
+    u64 : bool,
+    big : bool,
+}
+
+impl Ctx{
+    fn int_len(&self) -> usize{
+        if self.u64 { 8 } else { 4 }
+    }
+
+    fn bit_off(&self) -> usize{
+        if self.u64 { 32 } else { 16 }
+    }
+
+    fn ptr_len(&self) -> usize{
+        if self.u64 { 24 } else { 12 }
+    }
+
+    fn put(&self, buf : &mut [u8], off : usize, w : usize, val : i64){
+        let bytes = if self.big{
+            val.to_be_bytes()[8 - w..].to_vec()
+        } else {
+            val.to_le_bytes()[..w].to_vec()
+        };
+        buf[off..off + w].copy_from_slice(&bytes);
+    }
+
+    fn put_int(&self, buf : &mut [u8], off : usize, val : i64){
+        self.put(buf, off, self.int_len(), val);
+    }
+
+    fn sig(&self, val : i64) -> Vec<u8>{
+        let mut buf = vec![0; self.int_len()];
+        self.put_int(&mut buf, 0, val);
+        buf
+    }
+}
+
+const SIG_ROW_SIZE : i64 = 0xF7F7F7F7;
+const SIG_COL_SIZE : i64 = 0xF6F6F6F6;
+const SIG_SUB_HDR_CNT : i64 = -1024;
+const SIG_COL_TEXT : i64 = -3;
+const SIG_COL_NAME : i64 = -1;
+const SIG_COL_ATTR : i64 = -4;
+const SIG_FMT : i64 = -1026;
+const SIG_COL_LIST : i64 = -2;
+
+/// A subheader body together with the pointer compression and type fields.
+struct SubHdr{
+    body : Vec<u8>,
+    compression : u8,
+    ptype : u8,
+}
+
+impl SubHdr{
+    fn meta(body : Vec<u8>) -> SubHdr{
+        SubHdr{body, compression : 0, ptype : 0}
+    }
+}
+
+struct TextRef{
+    idx : usize,
+    off : usize,
+    len : usize,
+}
+
+impl Fixture{
+    fn ctx(&self) -> Ctx{
+        Ctx{u64 : self.u64, big : self.endianness == Endianness::Big}
+    }
+
+    /// Length of a row in bytes.
+    pub fn row_len(&self) -> usize{
+        self.columns.iter().map(|c| c.len()).sum()
+    }
+
+    fn hdr_len(&self) -> usize{
+        if self.u64 { 8192 } else { 1024 }
+    }
+
+    fn compression_literal(&self) -> &'static [u8;8]{
+        match self.compression{
+            Compression::None => b"\x00\x00\x00\x00\x00\x00\x00\x00",
+            Compression::Rle => b"SASYZCRL",
+            Compression::Rdc => b"SASYZCR2",
+        }
+    }
+
+    /// The bytes of an uncompressed row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a cell does not match its column, e.g. `Str` for a numeric.
+    pub fn encode_row(&self, row : &[Cell]) -> Vec<u8>{
+        let ctx = self.ctx();
+        let mut out = Vec::with_capacity(self.row_len());
+        for (col, cell) in self.columns.iter().zip(row){
+            match (&col.kind, cell){
+                (ColumnKind::Numeric(w), Cell::Num(x)) => {
+                    if ctx.big{
+                        out.extend_from_slice(&x.to_be_bytes()[..*w]);
+                    } else {
+                        out.extend_from_slice(&x.to_le_bytes()[8 - w..]);
+                    }
+                }
+                (ColumnKind::Character(w), Cell::Str(s)) => pad_into(&mut out, s.as_bytes(), *w),
+                (ColumnKind::Character(w), Cell::Bytes(s)) => pad_into(&mut out, s, *w),
+                (ColumnKind::Numeric(w), Cell::Bytes(s)) => pad_into(&mut out, s, *w),
+                _ => panic!("cell does not match column {}", col.name),
+            }
+        }
+        out
+    }
+
+    fn text_blocks(&self) -> (Vec<Vec<u8>>, Vec<[TextRef;3]>){
+        let ctx = self.ctx();
+        let il = ctx.int_len();
+        //The first text block starts with the compression literal and the
+        //creator proc, relative to the subheader start.
+        let mut first = vec![0u8; 64];
+        let lit_off = 16 + if self.u64 { 4 } else { 0 } - il;
+        first[lit_off..lit_off + 8].copy_from_slice(self.compression_literal());
+        let proc_off = match self.compression{
+            Compression::Rle => 40,
+            _ => 32,
+        } + if self.u64 { 4 } else { 0 } - il;
+        if self.compression != Compression::Rdc{
+            first[proc_off..proc_off + 8].copy_from_slice(b"DATASTEP");
+        }
+        let mut blocks = vec![first];
+        let mut refs = Vec::new();
+        let place = |blocks : &mut Vec<Vec<u8>>, s : &str| -> TextRef{
+            let bytes = s.as_bytes();
+            let mut padded = bytes.len();
+            padded += (4 - padded % 4) % 4;
+            if blocks.last().unwrap().len() + padded > 32000{
+                blocks.push(vec![0u8; 4]);
+            }
+            let idx = blocks.len() - 1;
+            let block = blocks.last_mut().unwrap();
+            let off = block.len();
+            block.extend_from_slice(bytes);
+            block.resize(off + padded, b' ');
+            TextRef{idx, off, len : bytes.len()}
+        };
+        for col in &self.columns{
+            let name = place(&mut blocks, &col.name);
+            let fmt = place(&mut blocks, &col.format);
+            let label = place(&mut blocks, &col.label);
+            refs.push([name, fmt, label]);
+        }
+        for block in blocks.iter_mut(){
+            let len = block.len() as i64;
+            let mut sz = [0u8; 2];
+            ctx.put(&mut sz, 0, 2, len);
+            block[0..2].copy_from_slice(&sz);
+        }
+        (blocks, refs)
+    }
+
+    fn meta_sub_hdrs(&self, mix_page_rows : usize) -> Vec<SubHdr>{
+        let ctx = self.ctx();
+        let il = ctx.int_len();
+        let mut subs = Vec::new();
+
+        let row_size_len = if self.u64 { 808 } else { 480 };
+        let mut rs = vec![0u8; row_size_len];
+        rs[0..il].copy_from_slice(&ctx.sig(SIG_ROW_SIZE));
+        ctx.put_int(&mut rs, 5 * il, self.row_len() as i64);
+        ctx.put_int(&mut rs, 6 * il, self.rows.len() as i64);
+        ctx.put_int(&mut rs, 9 * il, self.columns.len() as i64);
+        ctx.put_int(&mut rs, 10 * il, 0);
+        ctx.put_int(&mut rs, 15 * il, mix_page_rows as i64);
+        let (lcs_off, lcp_off) = if self.u64 { (682, 706) } else { (354, 378) };
+        ctx.put(&mut rs, lcs_off, 2, 0);
+        ctx.put(&mut rs, lcp_off, 2, 8);
+        subs.push(SubHdr::meta(rs));
+
+        let mut cs = vec![0u8; 3 * il];
+        cs[0..il].copy_from_slice(&ctx.sig(SIG_COL_SIZE));
+        ctx.put_int(&mut cs, il, self.columns.len() as i64);
+        subs.push(SubHdr::meta(cs));
+
+        if self.sub_hdr_counts{
+            let (lscv, start) = (5 * il, 9 * il);
+            let mut sc = vec![0u8; start + 12 * lscv];
+            sc[0..il].copy_from_slice(&ctx.sig(SIG_SUB_HDR_CNT));
+            ctx.put_int(&mut sc, il, 808);
+            ctx.put_int(&mut sc, 2 * il, 4);
+            ctx.put(&mut sc, 3 * il, 2, 7);
+            for (i, sig) in [SIG_COL_ATTR, SIG_COL_TEXT, SIG_COL_NAME, SIG_COL_LIST].iter().enumerate(){
+                let o = start + i * lscv;
+                let s = ctx.sig(*sig);
+                sc[o..o + il].copy_from_slice(&s);
+                ctx.put_int(&mut sc, o + il, 1);
+                ctx.put(&mut sc, o + 2 * il, 2, 1);
+                ctx.put_int(&mut sc, o + 3 * il, 1);
+                ctx.put(&mut sc, o + 4 * il, 2, 1);
+            }
+            subs.push(SubHdr::meta(sc));
+        }
+
+        let (blocks, refs) = self.text_blocks();
+        for block in &blocks{
+            let mut ct = ctx.sig(SIG_COL_TEXT);
+            ct.extend_from_slice(block);
+            subs.push(SubHdr::meta(ct));
+        }
+
+        let mut cn = vec![0u8; 2 * il + 12 + 8 * self.columns.len()];
+        cn[0..il].copy_from_slice(&ctx.sig(SIG_COL_NAME));
+        for (i, r) in refs.iter().enumerate(){
+            let o = il + 8 * (i + 1);
+            ctx.put(&mut cn, o, 2, r[0].idx as i64);
+            ctx.put(&mut cn, o + 2, 2, r[0].off as i64);
+            ctx.put(&mut cn, o + 4, 2, r[0].len as i64);
+        }
+        subs.push(SubHdr::meta(cn));
+
+        let mut ca = vec![0u8; 2 * il + 12 + (il + 8) * self.columns.len()];
+        ca[0..il].copy_from_slice(&ctx.sig(SIG_COL_ATTR));
+        let mut col_off = 0;
+        for (i, col) in self.columns.iter().enumerate(){
+            let o = il + 8 + i * (il + 8);
+            ctx.put_int(&mut ca, o, col_off as i64);
+            ctx.put(&mut ca, o + il, 4, col.len() as i64);
+            ca[o + il + 6] = match col.kind{
+                ColumnKind::Numeric(_) => 1,
+                ColumnKind::Character(_) => 2,
+            };
+            col_off += col.len();
+        }
+        subs.push(SubHdr::meta(ca));
+
+        for (col, r) in self.columns.iter().zip(&refs){
+            let mut f = vec![0u8; 3 * il + 40];
+            f[0..il].copy_from_slice(&ctx.sig(SIG_FMT));
+            ctx.put(&mut f, 3 * il, 2, i64::from(col.width));
+            ctx.put(&mut f, 3 * il + 2, 2, i64::from(col.decimals));
+            ctx.put(&mut f, 3 * il + 22, 2, r[1].idx as i64);
+            ctx.put(&mut f, 3 * il + 24, 2, r[1].off as i64);
+            ctx.put(&mut f, 3 * il + 26, 2, r[1].len as i64);
+            ctx.put(&mut f, 3 * il + 28, 2, r[2].idx as i64);
+            ctx.put(&mut f, 3 * il + 30, 2, r[2].off as i64);
+            ctx.put(&mut f, 3 * il + 32, 2, r[2].len as i64);
+            subs.push(SubHdr::meta(f));
+        }
+
+        if let Some(list) = &self.col_list{
+            let (cnt_off, val_off) = if self.u64 { (26, 50) } else { (14, 30) };
+            let mut cl = vec![0u8; val_off + 2 * list.len() + 8];
+            cl[0..il].copy_from_slice(&ctx.sig(SIG_COL_LIST));
+            ctx.put(&mut cl, cnt_off, 2, list.len() as i64);
+            for (i, v) in list.iter().enumerate(){
+                ctx.put(&mut cl, val_off + 2 * i, 2, i64::from(*v));
+            }
+            subs.push(SubHdr::meta(cl));
+        }
+        subs
+    }
+
+    fn row_sub_hdrs(&self) -> Vec<SubHdr>{
+        self.rows.iter().enumerate().map(|(i, row)| {
+            let raw = self.encode_row(row);
+            let packed = match self.compression{
+                Compression::Rle => rle_compress(&raw),
+                Compression::Rdc => rdc_compress(&raw),
+                Compression::None => raw.clone(),
+            };
+            let deleted = self.deleted.contains(&i);
+            if packed.len() < raw.len(){
+                SubHdr{ body : packed, compression : if deleted { 5 } else { 4 }, ptype : 1 }
+            } else {
+                SubHdr{body : raw, compression : 0, ptype : 1}
+            }
+        }).collect()
+    }
+
+    fn new_page(&self) -> Vec<u8>{
+        vec![0u8; self.page_len]
+    }
+
+    fn write_page_hdr(&self, page : &mut [u8], ptype : i64, blocks : usize, subs : usize){
+        let ctx = self.ctx();
+        let b = ctx.bit_off();
+        ctx.put(page, b, 2, ptype);
+        ctx.put(page, b + 2, 2, blocks as i64);
+        ctx.put(page, b + 4, 2, subs as i64);
+    }
+
+    /// Writes `subs` into `page` (pointers from the front, bodies from the
+    /// back). Returns the offset right behind the pointer table and the
+    /// lowest body offset.
+    fn place_sub_hdrs(&self, page : &mut [u8], subs : &[SubHdr]) -> (usize, usize){
+        let ctx = self.ctx();
+        let il = ctx.int_len();
+        let mut ptr = ctx.bit_off() + 8;
+        let mut end = page.len();
+        for s in subs{
+            end -= s.body.len();
+            end -= end % 8;
+            page[end..end + s.body.len()].copy_from_slice(&s.body);
+            ctx.put_int(page, ptr, end as i64);
+            ctx.put_int(page, ptr + il, s.body.len() as i64);
+            page[ptr + 2 * il] = s.compression;
+            page[ptr + 2 * il + 1] = s.ptype;
+            ptr += ctx.ptr_len();
+        }
+        (ptr, end)
+    }
+
+    /// Greedily packs as many subheaders as fit onto one page.
+    fn fitting(&self, subs : &[SubHdr], reserved : usize) -> usize{
+        let ctx = self.ctx();
+        let mut ptrs = ctx.bit_off() + 8;
+        let mut bodies = 0;
+        let mut n = 0;
+        for s in subs{
+            let need = s.body.len() + 8;
+            if ptrs + ctx.ptr_len() + bodies + need + reserved > self.page_len{
+                break;
+            }
+            ptrs += ctx.ptr_len();
+            bodies += need;
+            n += 1;
+        }
+        n
+    }
+
+    fn deleted_bitmap(&self, first_row : usize, n : usize) -> Vec<u8>{
+        let mut map = vec![0u8; n.div_ceil(8)];
+        for i in 0..n{
+            if self.deleted.contains(&(first_row + i)){
+                map[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        map
+    }
+
+    fn has_deleted(&self, first_row : usize, n : usize) -> bool{
+        self.deleted.iter().any(|d| *d >= first_row && *d < first_row + n)
+    }
+
+    /// The bytes of the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a cell does not match its column, or if a subheader does
+    /// not fit on a page.
+    pub fn to_bytes(&self) -> Vec<u8>{
+        let ctx = self.ctx();
+        let row_len = self.row_len();
+        let mut pages : Vec<Vec<u8>> = Vec::new();
+
+        if self.compression == Compression::None{
+            let data_start = ctx.bit_off() + 8;
+            let per_data_page = (self.page_len - data_start) * 8 / (8 * row_len + 1);
+            let mut mix_rows = 0;
+            if self.layout == Layout::Mix{
+                let probe = self.meta_sub_hdrs(0);
+                let sub_bytes : usize = probe.iter().map(|s| s.body.len() + 8).sum();
+                let ptr_end = data_start + probe.len() * ctx.ptr_len();
+                let start = ptr_end + ptr_end % 8;
+                let room = self.page_len.saturating_sub(start + sub_bytes + 8);
+                mix_rows = cmp::min(room * 8 / (8 * row_len + 1), self.rows.len());
+            }
+            let mut subs = self.meta_sub_hdrs(mix_rows);
+            let mut row = 0;
+            if self.layout == Layout::Mix{
+                let mut page = self.new_page();
+                let (ptr_end, _) = self.place_sub_hdrs(&mut page, &subs);
+                let start = ptr_end + ptr_end % 8;
+                for r in 0..mix_rows{
+                    let bytes = self.encode_row(&self.rows[r]);
+                    page[start + r * row_len..start + (r + 1) * row_len].copy_from_slice(&bytes);
+                }
+                let mut ptype = 512;
+                if self.has_deleted(0, mix_rows){
+                    ptype |= 0x80;
+                    let map = self.deleted_bitmap(0, mix_rows);
+                    let map_off = mix_rows * row_len;
+                    page[start + map_off..start + map_off + map.len()].copy_from_slice(&map);
+                    ctx.put(&mut page, if self.u64 { 24 } else { 12 }, 4, 8);
+                }
+                self.write_page_hdr(&mut page, ptype, subs.len() + mix_rows, subs.len());
+                pages.push(page);
+                row = mix_rows;
+            } else {
+                while !subs.is_empty(){
+                    let n = self.fitting(&subs, 0);
+                    assert!(n > 0, "subheader does not fit on a page");
+                    let mut page = self.new_page();
+                    let rest = subs.split_off(n);
+                    self.place_sub_hdrs(&mut page, &subs);
+                    self.write_page_hdr(&mut page, 0, subs.len(), subs.len());
+                    pages.push(page);
+                    subs = rest;
+                }
+            }
+            while row < self.rows.len(){
+                let n = cmp::min(per_data_page, self.rows.len() - row);
+                let mut page = self.new_page();
+                for r in 0..n{
+                    let bytes = self.encode_row(&self.rows[row + r]);
+                    let o = data_start + r * row_len;
+                    page[o..o + row_len].copy_from_slice(&bytes);
+                }
+                let mut ptype = 256;
+                if self.has_deleted(row, n){
+                    ptype |= 0x80;
+                    let map = self.deleted_bitmap(row, n);
+                    let map_off = n * row_len;
+                    page[data_start + map_off..data_start + map_off + map.len()].copy_from_slice(&map);
+                    ctx.put(&mut page, if self.u64 { 24 } else { 12 }, 4, 8);
+                }
+                self.write_page_hdr(&mut page, ptype, n, 0);
+                pages.push(page);
+                row += n;
+            }
+        } else {
+            let mut subs = self.meta_sub_hdrs(0);
+            let meta_cnt = subs.len();
+            subs.extend(self.row_sub_hdrs());
+            let mut first = true;
+            let mut placed = 0;
+            while placed < subs.len(){
+                let mut n = self.fitting(&subs[placed..], 0);
+                if first && self.layout == Layout::Data{
+                    n = cmp::min(n, meta_cnt);
+                }
+                assert!(n > 0, "subheader does not fit on a page");
+                let mut page = self.new_page();
+                self.place_sub_hdrs(&mut page, &subs[placed..placed + n]);
+                self.write_page_hdr(&mut page, 0, n, n);
+                pages.push(page);
+                placed += n;
+                first = false;
+            }
+        }
+
+        let mut out = self.header(pages.len());
+        for page in pages{
+            out.extend_from_slice(&page);
+        }
+        out
+    }
+
+    fn header(&self, page_count : usize) -> Vec<u8>{
+        let ctx = self.ctx();
+        let mut h = vec![0u8; self.hdr_len()];
+        let (a1, ta) = if self.u64 { (4, 8) } else { (0, 0) };
+        h[0..32].copy_from_slice(MAGIC);
+        h[32] = if self.u64 { 0x33 } else { 0x22 };
+        h[35] = if self.u64 { 0x33 } else { 0x22 };
+        h[37] = if ctx.big { 0x00 } else { 0x01 };
+        h[39] = b'1';
+        h[70] = self.encoding;
+        h[84..92].copy_from_slice(b"SAS FILE");
+        let mut name = self.name.as_bytes().to_vec();
+        name.resize(64, b' ');
+        h[92..156].copy_from_slice(&name);
+        h[156..164].copy_from_slice(b"DATA    ");
+        let stamp : f64 = 1_900_000_000.0;
+        let bytes = if ctx.big { stamp.to_be_bytes() } else { stamp.to_le_bytes() };
+        h[164 + a1..172 + a1].copy_from_slice(&bytes);
+        h[172 + a1..180 + a1].copy_from_slice(&bytes);
+        ctx.put(&mut h, 196 + a1, 4, self.hdr_len() as i64);
+        ctx.put(&mut h, 200 + a1, 4, self.page_len as i64);
+        ctx.put(&mut h, 204 + a1, ctx.int_len(), page_count as i64);
+        h[216 + ta..224 + ta].copy_from_slice(b"9.0401M0");
+        h[224 + ta..240 + ta].copy_from_slice(b"X64_7PRO\0\0\0\0\0\0\0\0");
+        h[240 + ta..256 + ta].copy_from_slice(b"6.1.7601\0\0\0\0\0\0\0\0");
+        h[256 + ta..272 + ta].copy_from_slice(b"x86_64\0\0\0\0\0\0\0\0\0\0");
+        h
+    }
+}
+
+
+fn pad_into(out : &mut Vec<u8>, bytes : &[u8], w : usize){
+    let n = cmp::min(bytes.len(), w);
+    out.extend_from_slice(&bytes[..n]);
+    out.resize(out.len() + w - n, b' ');
+}
+
+/// SASYZCRL encoder using the whole command set: byte runs, blank, zero and
+/// '@' runs, and literal copies of every length class.
+pub fn rle_compress(input : &[u8]) -> Vec<u8>{
+    let mut out = Vec::new();
+    let mut lit : Vec<u8> = Vec::new();
+    let flush = |out : &mut Vec<u8>, lit : &mut Vec<u8>| {
+        let mut rest = &lit[..];
+        while !rest.is_empty(){
+            let n = rest.len();
+            let take = if n >= 4160{
+                let t = n.min(8255);
+                out.push(0x10 | ((t - 4160) >> 8) as u8);
+                out.push(((t - 4160) & 0xFF) as u8);
+                t
+            } else if n >= 112{
+                out.push(((n - 64) >> 8) as u8);
+                out.push(((n - 64) & 0xFF) as u8);
+                n
+            } else if n >= 96{
+                out.push(0x20 | (n - 96) as u8);
+                n
+            } else if n >= 49{
+                let t = n.min(64);
+                out.push(0xB0 | (t - 49) as u8);
+                t
+            } else if n >= 33{
+                out.push(0xA0 | (n - 33) as u8);
+                n
+            } else if n >= 17{
+                out.push(0x90 | (n - 17) as u8);
+                n
+            } else {
+                out.push(0x80 | (n - 1) as u8);
+                n
+            };
+            out.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+        }
+        lit.clear();
+    };
+    let mut i = 0;
+    while i < input.len(){
+        let b = input[i];
+        let mut run = 1;
+        while i + run < input.len() && input[i + run] == b && run < 4113{
+            run += 1;
+        }
+        let special = matches!(b, 0x00 | 0x20 | 0x40);
+        if run >= 17 && special{
+            flush(&mut out, &mut lit);
+            let n = run - 17;
+            let cmd = match b { 0x40 => 0x50, 0x20 => 0x60, _ => 0x70 };
+            out.push(cmd | (n >> 8) as u8);
+            out.push((n & 0xFF) as u8);
+            i += run;
+        } else if run >= 18{
+            flush(&mut out, &mut lit);
+            let n = run - 18;
+            out.push(0x40 | (n >> 8) as u8);
+            out.push((n & 0xFF) as u8);
+            out.push(b);
+            i += run;
+        } else if run >= 2 && special{
+            flush(&mut out, &mut lit);
+            let cmd = match b { 0x40 => 0xD0, 0x20 => 0xE0, _ => 0xF0 };
+            out.push(cmd | (run - 2) as u8);
+            i += run;
+        } else if run >= 3{
+            flush(&mut out, &mut lit);
+            out.push(0xC0 | (run - 3) as u8);
+            out.push(b);
+            i += run;
+        } else {
+            lit.extend_from_slice(&input[i..i + run]);
+            i += run;
+        }
+    }
+    flush(&mut out, &mut lit);
+    out
+}
+
+/// SASYZCR2 (Ross data compression) encoder with literals, short and long
+/// runs, and short and long back references (overlapping ones included).
+pub fn rdc_compress(input : &[u8]) -> Vec<u8>{
+    let mut out = Vec::new();
+    let mut ctrl_pos = 0;
+    let mut ctrl_bits : u16 = 0;
+    let mut ctrl_cnt = 16;
+    let mut i = 0;
+    while i < input.len(){
+        if ctrl_cnt == 16{
+            if !out.is_empty(){
+                out[ctrl_pos] = (ctrl_bits >> 8) as u8;
+                out[ctrl_pos + 1] = (ctrl_bits & 0xFF) as u8;
+            }
+            ctrl_pos = out.len();
+            out.push(0);
+            out.push(0);
+            ctrl_bits = 0;
+            ctrl_cnt = 0;
+        }
+        let b = input[i];
+        let mut run = 1;
+        while i + run < input.len() && input[i + run] == b && run < 4114{
+            run += 1;
+        }
+        //longest earlier match
+        let (mut best_len, mut best_ofs) = (0, 0);
+        let window = i.min(4098);
+        for ofs in 3..=window{
+            let mut l = 0;
+            while i + l < input.len() && l < 271 && input[i + l] == input[i + l - ofs]{
+                l += 1;
+            }
+            if l > best_len{
+                best_len = l;
+                best_ofs = ofs;
+            }
+        }
+        let flag = 0x8000 >> ctrl_cnt;
+        if run >= 3 && run >= best_len{
+            ctrl_bits |= flag;
+            if run <= 18{
+                out.push((run - 3) as u8);
+                out.push(b);
+            } else {
+                let n = run - 19;
+                out.push(0x10 | (n & 0x0F) as u8);
+                out.push((n >> 4) as u8);
+                out.push(b);
+            }
+            i += run;
+        } else if best_len >= 3{
+            ctrl_bits |= flag;
+            let o = best_ofs - 3;
+            if best_len <= 15{
+                out.push(((best_len as u8) << 4) | (o & 0x0F) as u8);
+                out.push((o >> 4) as u8);
+            } else {
+                let l = best_len.max(16);
+                out.push(0x20 | (o & 0x0F) as u8);
+                out.push((o >> 4) as u8);
+                out.push((l - 16) as u8);
+            }
+            i += best_len;
+        } else {
+            out.push(b);
+            i += 1;
+        }
+        ctrl_cnt += 1;
+    }
+    if !out.is_empty(){
+        out[ctrl_pos] = (ctrl_bits >> 8) as u8;
+        out[ctrl_pos + 1] = (ctrl_bits & 0xFF) as u8;
+    }
+    out
+}
@@ -0,0 +1,325 @@
+//! SAS display formats: `ColumnFormat` parses them and holds the format of
+//! each column, read from its format subheader, and `SasVal::format` renders
+//! a value the way SAS shows it.
+//!
+//! ```no_run
+//! let mut sas = sas::SAS7bdat::open("data.sas7bdat").unwrap();
+//! let formats = sas.col_format_specs().clone();
+//! while sas.read_line().unwrap() {
+//!     let shown : Vec<String> = sas.row_vals.iter().zip(&formats).map(|(x, f)| x.format(f)).collect();
+//!     println!("{}", shown.join(" "));
+//! }
+//! ```
+use std::fmt;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, Timelike};
+use crate::{temporal, SasVal, Temporal};
+
+const MONTHS : [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+/// Display format of a column, e.g. `COMMA12.2`, with the name `COMMA`, the
+/// width 12 and 2 decimals. A width of 0 stands for the default width of
+/// the format.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColumnFormat{
+    pub name : String,
+    pub width : u16,
+    pub decimals : u16,
+}
+
+impl ColumnFormat{
+    pub fn new(name : &str, width : u16, decimals : u16) -> ColumnFormat{
+        ColumnFormat{name : name.to_ascii_uppercase(), width, decimals}
+    }
+
+    /// Parses a format as written in SAS, e.g. `COMMA12.2`, `Z5.`, `8.2`,
+    /// `$CHAR10.` or `BEST`. None if the text is not a format.
+    pub fn parse(text : &str) -> Option<ColumnFormat>{
+        let text = text.trim();
+        let (head, decimals) = match text.split_once('.'){
+            Some((head, "")) => (head, 0),
+            Some((head, decimals)) => (head, decimals.parse().ok()?),
+            None => (text, 0),
+        };
+        //names may contain digits, as E8601DA, but do not end with one
+        let name = head.trim_end_matches(|x : char| x.is_ascii_digit());
+        let width = match &head[name.len()..]{
+            "" => 0,
+            width => width.parse().ok()?,
+        };
+        let valid = name.char_indices().all(|(i, x)| x.is_ascii_alphanumeric() || x == '_' || (x == '$' && i == 0));
+        if !valid || (name.is_empty() && width == 0) {
+            return None;
+        }
+        Some(ColumnFormat::new(name, width, decimals))
+    }
+}
+
+impl fmt::Display for ColumnFormat{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", self.name)?;
+        if self.width > 0 {
+            write!(f, "{}", self.width)?;
+        }
+        write!(f, ".")?;
+        if self.decimals > 0 {
+            write!(f, "{}", self.decimals)?;
+        }
+        Ok(())
+    }
+}
+
+impl SasVal{
+    /// The value as SAS displays it with `fmt`, without the blanks SAS pads
+    /// it with up to the width. Covers the numeric formats `w.d`, `BEST`,
+    /// `COMMA`, `DOLLAR`, `PERCENT` and `Z`, the date, datetime and time
+    /// formats `DATE`, `DDMMYY`, `MMDDYY`, `YYMMDD`, `MONYY`, `YEAR`,
+    /// `DATETIME`, `TIME`, `HHMM`, `TOD` and `E8601DA`/`DT`/`TM`, and the
+    /// character formats `$`, `$CHAR` and `$UPCASE`. Other formats show
+    /// numbers as `BEST12.`. A value that does not fit the width is shown
+    /// with fewer digits, as `BEST`, or as asterisks, as in SAS.
+    pub fn format(&self, fmt : &ColumnFormat) -> String{
+        let x = match self{
+            SasVal::Text(x) => return text(x, fmt),
+            SasVal::Bytes(x) => return text(&String::from_utf8_lossy(x), fmt),
+            SasVal::Numeric(x) => *x,
+            SasVal::Date(x) => (*x - epoch()).num_days() as f64,
+            SasVal::DateTime(x) => (x.naive_utc() - epoch().and_time(NaiveTime::MIN)).num_milliseconds() as f64 / 1000.0,
+            SasVal::Time(x) => x.num_seconds_from_midnight() as f64 + x.nanosecond() as f64 / 1e9,
+        };
+        if x.is_nan() {
+            return match self.missing_tag(){
+                Some(tag) => format!(".{tag}"),
+                None => ".".to_string(),
+            };
+        }
+        let d = usize::from(fmt.decimals);
+        let name = fmt.name.as_str();
+        let w = match fmt.width{
+            0 => default_width(name),
+            w => usize::from(w),
+        };
+        let text = match (name, temporal(name)){
+            ("" | "F", _) if fmt.width == 0 => Some(best(x, w)),
+            ("" | "F", _) => Some(fit(fixed(x, d), w, x)),
+            ("COMMA", _) => Some(fit(comma(x, d, ""), w, x)),
+            ("DOLLAR", _) => Some(fit(comma(x, d, "$"), w, x)),
+            ("PERCENT", _) => Some(fit(percent(x, d), w, x)),
+            ("Z", _) => Some(fit(zeros(x, d, w), w, x)),
+            (_, Some(Temporal::Date)) => date(x, name, w),
+            (_, Some(Temporal::DateTime)) => datetime(x, name, w, d),
+            (_, Some(Temporal::Time)) => time(x, name, w, d),
+            _ => Some(best(x, w)),
+        };
+        text.unwrap_or_else(|| "*".repeat(w))
+    }
+}
+
+//width of a format written without one, e.g. DATE.
+fn default_width(name : &str) -> usize{
+    match name{
+        "Z" => 1,
+        "YEAR" => 4,
+        "MONYY" | "HHMM" => 5,
+        "COMMA" | "DOLLAR" | "PERCENT" => 6,
+        "DATE" => 7,
+        "DDMMYY" | "MMDDYY" | "YYMMDD" | "TIME" | "TOD" | "E8601TM" => 8,
+        "E8601DA" => 10,
+        "DATETIME" => 16,
+        "E8601DT" => 19,
+        _ => 12,
+    }
+}
+
+fn epoch() -> NaiveDate{
+    NaiveDate::from_ymd_opt(1960, 1, 1).unwrap()
+}
+
+fn text(x : &str, fmt : &ColumnFormat) -> String{
+    let x = match fmt.name.as_str(){
+        "$UPCASE" => x.to_uppercase(),
+        _ => x.to_string(),
+    };
+    match fmt.width{
+        0 => x,
+        w => x.chars().take(usize::from(w)).collect(),
+    }
+}
+
+//x with d decimals, without the sign of a value that rounds to zero. Halves
+//are rounded away from zero as in SAS, format! rounds them to even.
+fn fixed(x : f64, d : usize) -> String{
+    let scale = 10f64.powi(d as i32);
+    let x = if (x * scale).abs() < 1e15 { (x * scale).round() / scale } else { x };
+    let text = format!("{x:.d$}");
+    match text.strip_prefix('-'){
+        Some(abs) if !nonzero(abs) => abs.to_string(),
+        _ => text,
+    }
+}
+
+fn nonzero(text : &str) -> bool{
+    text.bytes().any(|x| (b'1'..=b'9').contains(&x))
+}
+
+//the formatted value if it fits the width, else BEST
+fn fit(text : String, w : usize, x : f64) -> String{
+    if text.len() <= w {
+        text
+    } else {
+        best(x, w)
+    }
+}
+
+//SAS picks the notation that shows the most significant digits in w
+//characters, plain if both show as many
+fn best(x : f64, w : usize) -> String{
+    if x == 0.0 {
+        return "0".to_string();
+    }
+    let mut found : Vec<String> = Vec::new();
+    for d in (0..w).rev(){
+        let text = trim_zeros(format!("{x:.d$E}"));
+        if text.len() <= w {
+            found.push(text);
+            break;
+        }
+    }
+    let int_len = format!("{:.0}", x.abs().trunc()).len() + usize::from(x < 0.0);
+    if int_len <= w {
+        let text = trim_zeros(fixed(x, w.saturating_sub(int_len + 1)));
+        if text.len() <= w {
+            found.push(text);
+        }
+    }
+    let digits = |text : &String| text.split('E').next().unwrap_or("").bytes()
+        .filter(u8::is_ascii_digit)
+        .skip_while(|x| *x == b'0')
+        .count();
+    found.into_iter().max_by_key(digits).unwrap_or_else(|| "*".repeat(w))
+}
+
+//drops the zeros at the end of the decimals, and the point if none are left
+fn trim_zeros(text : String) -> String{
+    let (mantissa, exp) = match text.find('E'){
+        Some(i) => text.split_at(i),
+        None => (text.as_str(), ""),
+    };
+    if !mantissa.contains('.') {
+        return text;
+    }
+    format!("{}{exp}", mantissa.trim_end_matches('0').trim_end_matches('.'))
+}
+
+fn comma(x : f64, d : usize, prefix : &str) -> String{
+    let abs = fixed(x.abs(), d);
+    let (int, decimals) = match abs.split_once('.'){
+        Some((int, decimals)) => (int, format!(".{decimals}")),
+        None => (abs.as_str(), String::new()),
+    };
+    let mut grouped = String::new();
+    for (i, x) in int.chars().enumerate(){
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(x);
+    }
+    let sign = if x < 0.0 && nonzero(&abs) { "-" } else { "" };
+    format!("{sign}{prefix}{grouped}{decimals}")
+}
+
+//negative percentages are put in parentheses
+fn percent(x : f64, d : usize) -> String{
+    let text = fixed(x * 100.0, d);
+    match text.strip_prefix('-'){
+        Some(abs) => format!("({abs}%)"),
+        None => format!("{text}%"),
+    }
+}
+
+fn zeros(x : f64, d : usize, w : usize) -> String{
+    let abs = fixed(x.abs(), d);
+    if x < 0.0 && nonzero(&abs) {
+        format!("-{abs:0>0$}", w.saturating_sub(1))
+    } else {
+        format!("{abs:0>w$}")
+    }
+}
+
+fn date(days : f64, name : &str, w : usize) -> Option<String>{
+    let date = epoch().checked_add_signed(TimeDelta::try_days(days.floor() as i64)?)?;
+    let dd = format!("{:02}", date.day());
+    let mm = format!("{:02}", date.month());
+    let mon = MONTHS[date.month0() as usize];
+    let yyyy = format!("{:04}", date.year());
+    let yy = format!("{:02}", date.year().rem_euclid(100));
+    let text = match (name, w){
+        ("DATE", 5..=6) => format!("{dd}{mon}"),
+        ("DATE", 7..=8) => format!("{dd}{mon}{yy}"),
+        ("DATE", 9..=10) => format!("{dd}{mon}{yyyy}"),
+        ("DATE", 11..) => format!("{dd}-{mon}-{yyyy}"),
+        ("DDMMYY", 6..=7) => format!("{dd}{mm}{yy}"),
+        ("DDMMYY", 8..=9) => format!("{dd}/{mm}/{yy}"),
+        ("DDMMYY", 10..) => format!("{dd}/{mm}/{yyyy}"),
+        ("MMDDYY", 6..=7) => format!("{mm}{dd}{yy}"),
+        ("MMDDYY", 8..=9) => format!("{mm}/{dd}/{yy}"),
+        ("MMDDYY", 10..) => format!("{mm}/{dd}/{yyyy}"),
+        ("YYMMDD", 6..=7) => format!("{yy}{mm}{dd}"),
+        ("YYMMDD", 8..=9) => format!("{yy}-{mm}-{dd}"),
+        ("YYMMDD", 10..) | ("E8601DA", 10..) => format!("{yyyy}-{mm}-{dd}"),
+        ("MONYY", 5..=6) => format!("{mon}{yy}"),
+        ("MONYY", 7..) => format!("{mon}{yyyy}"),
+        ("YEAR", 2..=3) => yy,
+        ("YEAR", 4..) => yyyy,
+        _ => return None,
+    };
+    Some(text)
+}
+
+//whole seconds and the decimals, rounded to d of them
+fn split_seconds(secs : f64, d : usize) -> (i64, String){
+    let d = d.min(6);
+    let scale = 10i64.pow(d as u32);
+    let units = (secs * scale as f64).round() as i64;
+    let decimals = match d{
+        0 => String::new(),
+        _ => format!(".{:0d$}", units.rem_euclid(scale)),
+    };
+    (units.div_euclid(scale), decimals)
+}
+
+fn datetime(secs : f64, name : &str, w : usize, d : usize) -> Option<String>{
+    let (whole, decimals) = split_seconds(secs, d);
+    let at = epoch().and_time(NaiveTime::MIN).checked_add_signed(TimeDelta::try_seconds(whole)?)?;
+    let date = (at.date() - epoch()).num_days() as f64;
+    let text = match (name, w){
+        ("DATETIME", 13..=15) => format!("{}:{}", self::date(date, "DATE", 7)?, at.format("%H:%M")),
+        ("DATETIME", 16..=17) => format!("{}:{}{decimals}", self::date(date, "DATE", 7)?, at.format("%H:%M:%S")),
+        ("DATETIME", 18..) => format!("{}:{}{decimals}", self::date(date, "DATE", 9)?, at.format("%H:%M:%S")),
+        ("E8601DT", 16..) => format!("{}{decimals}", at.format("%Y-%m-%dT%H:%M:%S")),
+        _ => return None,
+    };
+    Some(text)
+}
+
+fn time(secs : f64, name : &str, w : usize, d : usize) -> Option<String>{
+    let (whole, decimals) = split_seconds(secs, d);
+    let sign = if whole < 0 { "-" } else { "" };
+    let (h, m, s) = (whole.abs() / 3600, whole.abs() / 60 % 60, whole.abs() % 60);
+    let text = match (name, w){
+        //durations, the hours are not bounded
+        ("TIME", 5..=7) => format!("{sign}{h}:{m:02}"),
+        ("TIME", 8..) => format!("{sign}{h}:{m:02}:{s:02}{decimals}"),
+        ("HHMM", 4..) => {
+            let minutes = (secs.abs() / 60.0).round() as i64;
+            format!("{sign}{}:{:02}", minutes / 60, minutes % 60)
+        }
+        //times of day
+        ("TOD", 8..) | ("E8601TM", 8..) => {
+            let at = DateTime::from_timestamp(whole.rem_euclid(86400), 0)?;
+            format!("{}{decimals}", at.format("%H:%M:%S"))
+        }
+        _ => return None,
+    };
+    Some(text)
+}
@@ -0,0 +1,134 @@
+//! JSON and JSON Lines output, with the `json` feature.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//! use sas::json::JsonOptions;
+//!
+//! let mut sas = sas::SAS7bdat::open("data.sas7bdat").unwrap();
+//! let out = BufWriter::new(File::create("data.json").unwrap());
+//! JsonOptions::default().labels(true).write_document(&mut sas, out).unwrap();
+//! ```
+//!
+//! Numbers are JSON numbers and missing values `null`, dates, datetimes and
+//! times ISO-8601 strings. Rows are objects keyed by the column names. The
+//! schema of `write_document` gives the type of each column, its format as
+//! a `ColumnFormat` and, on request, its label.
+use std::io::{Read, Write};
+use serde::Serialize;
+use serde::ser::{SerializeMap, Serializer};
+use crate::{ColumnFormat, Encoding, SAS7bdat, SasError, SasVal};
+
+//column types for numbers, dates, datetimes, times, raw text and text
+const TYPES : [&str; 6] = ["number", "date", "datetime", "time", "bytes", "string"];
+
+/// Options of the JSON writers.
+#[derive(Clone, Debug, Default)]
+pub struct JsonOptions{
+    labels : bool,
+    special_missing : bool,
+}
+
+impl JsonOptions{
+    /// Adds the column labels to the schema of `write_document`.
+    pub fn labels(mut self, labels : bool) -> JsonOptions{
+        self.labels = labels;
+        self
+    }
+
+    /// Writes special missing values as strings, `".A"` to `".Z"` and
+    /// `"._"`, instead of `null`.
+    pub fn special_missing(mut self, special_missing : bool) -> JsonOptions{
+        self.special_missing = special_missing;
+        self
+    }
+
+    /// Writes the remaining rows as JSON Lines, one object per row. Returns
+    /// the number of rows written.
+    pub fn write_lines<R : Read, W : Write>(&self, sas : &mut SAS7bdat<R>, mut out : W) -> Result<usize, SasError>{
+        let mut rows = 0;
+        while sas.read_line()?{
+            serde_json::to_writer(&mut out, &self.row(sas)).map_err(to_sas)?;
+            out.write_all(b"\n").map_err(SasError::Io)?;
+            rows += 1;
+        }
+        out.flush().map_err(SasError::Io)?;
+        Ok(rows)
+    }
+
+    /// Writes one JSON document, `{"schema" : {...}, "data" : [...]}`, with
+    /// the remaining rows in `data`. The rows are streamed, not collected.
+    /// Returns the number of rows written.
+    pub fn write_document<R : Read, W : Write>(&self, sas : &mut SAS7bdat<R>, mut out : W) -> Result<usize, SasError>{
+        out.write_all(b"{\"schema\":").map_err(SasError::Io)?;
+        serde_json::to_writer(&mut out, &self.schema(sas)).map_err(to_sas)?;
+        out.write_all(b",\"data\":[").map_err(SasError::Io)?;
+        let mut rows = 0;
+        while sas.read_line()?{
+            if rows > 0 {
+                out.write_all(b",").map_err(SasError::Io)?;
+            }
+            serde_json::to_writer(&mut out, &self.row(sas)).map_err(to_sas)?;
+            rows += 1;
+        }
+        out.write_all(b"]}").map_err(SasError::Io)?;
+        out.flush().map_err(SasError::Io)?;
+        Ok(rows)
+    }
+
+    fn row<'a, R : Read>(&self, sas : &'a SAS7bdat<R>) -> Row<'a>{
+        Row{names : &sas.col_names, vals : &sas.row_vals, special_missing : self.special_missing}
+    }
+
+    fn schema<'a, R : Read>(&self, sas : &'a SAS7bdat<R>) -> Schema<'a>{
+        let columns = sas.export_types(&TYPES).into_iter().enumerate().map(|(j, kind)| ColumnSchema{
+            name : &sas.col_names[j],
+            kind,
+            format : sas.col_format_specs.get(j).filter(|x| !x.name.is_empty()),
+            label : Some(sas.col_labels[j].as_str()).filter(|x| self.labels && !x.is_empty()),
+        }).collect();
+        Schema{name : sas.name(), encoding : sas.encoding(), rows : sas.row_count, columns}
+    }
+}
+
+fn to_sas(er : serde_json::Error) -> SasError{
+    SasError::Io(er.into())
+}
+
+#[derive(Serialize)]
+struct Schema<'a>{
+    name : &'a str,
+    encoding : Encoding,
+    rows : usize,
+    columns : Vec<ColumnSchema<'a>>,
+}
+
+#[derive(Serialize)]
+struct ColumnSchema<'a>{
+    name : &'a str,
+    #[serde(rename = "type")]
+    kind : &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format : Option<&'a ColumnFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label : Option<&'a str>,
+}
+
+struct Row<'a>{
+    names : &'a [String],
+    vals : &'a [SasVal],
+    special_missing : bool,
+}
+
+impl Serialize for Row<'_>{
+    fn serialize<S : Serializer>(&self, s : S) -> Result<S::Ok, S::Error>{
+        let mut map = s.serialize_map(Some(self.vals.len()))?;
+        for (name, val) in self.names.iter().zip(self.vals){
+            match val.missing_tag(){
+                Some(tag) if self.special_missing => map.serialize_entry(name, &format!(".{tag}"))?,
+                _ => map.serialize_entry(name, val)?,
+            }
+        }
+        map.end()
+    }
+}
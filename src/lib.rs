@@ -1,12 +1,46 @@
 use std::collections::HashMap;
-use std::io::{SeekFrom, Read, Seek, BufReader};
+use std::io::{Seek, BufReader};
 use std::cmp;
-use encoding::{Encoding, DecoderTrap};
-use encoding::all::*;
-use encoding::codec::singlebyte::SingleByteEncoding;
-use encoding::codec::utf_8::UTF8Encoding;
 use chrono::prelude::*;
-use chrono::{Duration, Utc, NaiveDate};
+use chrono::{Duration, Utc, NaiveDate, NaiveTime};
+
+mod codec;
+mod codepages;
+mod encodings;
+mod source;
+mod range;
+mod validate;
+mod prefetch;
+pub mod postgres;
+mod batch;
+mod formats;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "fixture")]
+pub mod fixture;
+#[cfg(feature = "tokio")]
+mod async_reader;
+#[cfg(feature = "polars")]
+pub mod polars;
+#[cfg(feature = "datafusion")]
+pub mod datafusion;
+pub use codec::{Codec, decompress, open_reader};
+pub use encodings::{DecodePolicy, Encoding, EncodingGuess};
+pub use range::{HttpClient, HttpRange, RangeReader, RangeSource};
+pub use validate::{validate, Check, Issue, ValidationReport};
+pub use prefetch::PrefetchReader;
+pub use batch::{BatchColumn, ColumnBatch, Validity};
+pub use formats::ColumnFormat;
+use batch::TextOpts;
+#[cfg(feature = "http")]
+pub use range::UreqClient;
+#[cfg(feature = "tokio")]
+pub use async_reader::AsyncSAS7bdat;
+use source::{Page, Source};
 
 #[derive(Default, Debug)]
 enum Endian{
@@ -21,15 +55,33 @@ pub enum SasVal{
     Text(String),
     Date(NaiveDate),
     DateTime(DateTime<Utc>),
+    Time(NaiveTime),
+    Bytes(Vec<u8>),
 }
 
-type Decompressor = fn(usize, &[u8]) -> Result<Vec<u8>, SasError>;
+impl SasVal{
+    /// Letter of a special missing value, `A` to `Z` for `.A` to `.Z` and `_`
+    /// for `._`. None for other values, including the ordinary missing value.
+    pub fn missing_tag(&self) -> Option<char>{
+        match self{
+            //SAS keeps the complemented letter in the third byte of the NaN
+            SasVal::Numeric(x) if x.is_nan() => {
+                let tag = !((x.to_bits() >> 40) as u8);
+                matches!(tag, b'A'..=b'Z' | b'_').then_some(tag as char)
+            }
+            _ => None,
+        }
+    }
+}
+
+type Decompressor = fn(&[u8], &mut [u8]) -> Result<(), SasError>;
 
 //#[derive(Default)]
 pub struct SAS7bdat<R>{
     date_base : DateTime<Utc>,
     pub row_vals : Vec<SasVal>,
     col_formats : Vec<String>,
+    col_format_specs : Vec<ColumnFormat>,
     trim_strings : bool,
     no_align_correction : bool,
     date_created : f64,
@@ -41,22 +93,38 @@ pub struct SAS7bdat<R>{
     os_type : String,
     os_name : String,
     file_type : String,
-    file_encoding : String,
+    opts : ReadOptions,
+    encoding_id : u8,
     u64 : bool,
     byte_order : Endian,
     compression : String,
-    text_decoder : Encodings,
+    text_decoder : Encoding,
     row_count : usize,
     col_types : Vec<u16>,
     col_labels : Vec<String>,
     pub col_names : Vec<String>,
+    col_list : Vec<i16>,
+    sub_hdr_counts : Vec<SubHdrCount>,
+    decode_errors : Vec<usize>,
     buf : Vec<u8>,
-    float_buf : [u8;8],
-    buf_rdr : BufReader<R>,
-    cached_page : Vec<u8>,
+    plan : Vec<ColPlan>,
+    batch : Option<ColumnBatch>,
+    //error ending a batch, returned by the next read_batch_columns
+    batch_err : Option<SasError>,
+    src : Source<R>,
+    cached_page : Page,
+    row_buf : Vec<u8>,
+    cur_row : Option<(usize, usize)>,
     cur_page_type : isize,
     cur_page_block_count : usize,
     cur_page_sub_hdr_count : usize,
+    cur_page_has_deleted : bool,
+    cur_row_deleted : bool,
+    deleted_count : usize,
+    pages_read : usize,
+    eof : bool,
+    damage : DamageReport,
+    damaged_rows : usize,
     cur_row_in_file_idx : usize,
     cur_row_on_page_idx : usize,
     cur_page_data_sub_hdr_pointers : Vec<SubHdrPtr>,
@@ -64,7 +132,6 @@ pub struct SAS7bdat<R>{
     col_name_strings : Vec<Vec<u8>>,
     col_data_off : Vec<usize>,
     col_data_lens : Vec<usize>,
-    cols : Vec<Col>,
     props : SasProperties,
     hdr_sig_map : HashMap<Vec<u8>, usize>, 
 }
@@ -87,14 +154,103 @@ struct SasProperties{
     col_cnt : usize,
 }
 
-#[derive(Default)]
-struct Col {
-    col_id : usize,
-    name : String,
-    label : String,
-    fmt : String,
-    ctype : u16,
+//How the 8 bytes of a double are read from a numeric cell. Cells of fewer
+//than 8 bytes hold the high order bytes.
+#[derive(Clone, Copy, Debug)]
+enum NumOp{
+    Le,
+    Be,
+    ShortLe,
+    ShortBe,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum CellOp{
+    Num(NumOp),
+    Date(NumOp),
+    DateTime(NumOp),
+    Time(NumOp),
+    Text,
+}
+
+//Decoding of one column, worked out from the metadata once instead of for
+//every cell.
+#[derive(Clone, Copy, Debug)]
+struct ColPlan{
+    off : usize,
     len : usize,
+    op : CellOp,
+}
+
+impl<R> SAS7bdat<R>{
+    //Type of each column in an export, `names` being its types for numbers,
+    //dates, datetimes, times, raw text and text.
+    fn export_types(&self, names : &[&'static str; 6]) -> Vec<&'static str>{
+        let raw = self.opts.decode_policy == DecodePolicy::Raw;
+        self.plan.iter().map(|x| match x.op{
+            CellOp::Num(_) => names[0],
+            CellOp::Date(_) => names[1],
+            CellOp::DateTime(_) => names[2],
+            CellOp::Time(_) => names[3],
+            CellOp::Text if raw => names[4],
+            CellOp::Text => names[5],
+        }).collect()
+    }
+}
+
+//double quotes, as SQL identifiers
+fn quote_ident(name : &str) -> String{
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn read_num(op : NumOp, bytes : &[u8]) -> f64{
+    let mut buf = [0u8;8];
+    match op{
+        NumOp::Le => {
+            buf.copy_from_slice(bytes);
+            f64::from_le_bytes(buf)
+        }
+        NumOp::Be => {
+            buf.copy_from_slice(bytes);
+            f64::from_be_bytes(buf)
+        }
+        NumOp::ShortLe => {
+            buf[8 - bytes.len()..].copy_from_slice(bytes);
+            f64::from_le_bytes(buf)
+        }
+        NumOp::ShortBe => {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            f64::from_be_bytes(buf)
+        }
+    }
+}
+
+//Decodes a character cell, counting the cells that failed or needed
+//replacements.
+fn decode_text(decoder : &Encoding, policy : DecodePolicy, errors : &mut usize, bytes : &[u8]) -> Result<String, SasError>{
+    match decoder.decode_cell(bytes, policy){
+        Ok((val, failed)) => {
+            *errors += usize::from(failed);
+            Ok(val)
+        }
+        Err(er) => {
+            *errors += 1;
+            Err(er)
+        }
+    }
+}
+
+/// Where the subheaders with one signature are stored, from the
+/// subheader-counts subheader. Pages count from 1 and positions are indices
+/// of subheader pointers on the page, 0 if there is no such subheader.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Clone, Debug)]
+pub struct SubHdrCount{
+    pub signature : Vec<u8>,
+    pub first_page : usize,
+    pub first_pos : usize,
+    pub last_page : usize,
+    pub last_pos : usize,
 }
 
 #[derive(Default)]
@@ -175,6 +331,10 @@ const HEADER_SIZE_OFFSET : usize = 196;
 const HEADER_SIZE_LENGTH : usize = 4;
 const PAGE_SIZE_OFFSET : usize = 200;
 const PAGE_SIZE_LENGTH : usize = 4;
+//page lengths outside these bounds, and header lengths above the maximum,
+//are taken as a corrupt header, rather than allocating the page
+const MIN_PAGE_LEN : usize = 64;
+const MAX_PAGE_LEN : usize = 1 << 26;
 const PAGE_COUNT_OFFSET : usize = 204;
 const PAGE_COUNT_LENGTH : usize = 4;
 const SAS_RELEASE_OFFSET : usize = 216;
@@ -203,6 +363,11 @@ const PAGE_AMD_TYPE : isize = 1024;
 const SUBHEADER_POINTERS_OFFSET : usize = 8;
 const TRUNCATED_SUBHEADER_ID : usize = 1;
 const COMPRESSED_SUBHEADER_ID : usize = 4;
+const DELETED_SUBHEADER_ID : usize = 5;
+const PAGE_DELETED_FLAG : isize = 128;
+const PAGE_DELETED_POINTER_OFFSET_X86 : usize = 12;
+const PAGE_DELETED_POINTER_OFFSET_X64 : usize = 24;
+const PAGE_DELETED_POINTER_LENGTH : usize = 4;
 const COMPRESSED_SUBHEADER_TYPE : usize = 1;
 const TEXT_BLOCK_SIZE_LENGTH : usize = 2;
 const ROW_LENGTH_OFFSET_MULTIPLIER : usize = 5;
@@ -222,6 +387,8 @@ const COLUMN_DATA_LENGTH_OFFSET : usize = 8;
 const COLUMN_DATA_LENGTH_LENGTH : usize = 4;
 const COLUMN_TYPE_OFFSET : usize = 14;
 const COLUMN_TYPE_LENGTH : usize = 1;
+const COLUMN_FORMAT_WIDTH_OFFSET : usize = 0;
+const COLUMN_FORMAT_DECIMALS_OFFSET : usize = 2;
 const COLUMN_FORMAT_TEXT_SUBHEADER_INDEX_OFFSET : usize = 22;
 const COLUMN_FORMAT_TEXT_SUBHEADER_INDEX_LENGTH : usize = 2;
 const COLUMN_FORMAT_OFFSET_OFFSET : usize = 24;
@@ -234,6 +401,15 @@ const COLUMN_LABEL_OFFSET_OFFSET : usize = 30;
 const COLUMN_LABEL_OFFSET_LENGTH : usize = 2;
 const COLUMN_LABEL_LENGTH_OFFSET : usize = 32;
 const COLUMN_LABEL_LENGTH_LENGTH : usize = 2;
+const COLUMN_LIST_COUNT_OFFSET_X86 : usize = 14;
+const COLUMN_LIST_COUNT_OFFSET_X64 : usize = 26;
+const COLUMN_LIST_VALUES_OFFSET_X86 : usize = 30;
+const COLUMN_LIST_VALUES_OFFSET_X64 : usize = 50;
+const COLUMN_LIST_VALUE_LENGTH : usize = 2;
+const SUBHEADER_COUNTS_VECTORS_OFFSET_MULTIPLIER : usize = 9;
+const SUBHEADER_COUNTS_VECTOR_LENGTH_MULTIPLIER : usize = 5;
+const SUBHEADER_COUNTS_VECTORS : usize = 12;
+const SUBHEADER_COUNTS_POS_LENGTH : usize = 2;
 
 trait ByteNum {
     fn from_bytes(bytes : &[u8], off : usize, w : usize, endi : &Endian) -> Self;
@@ -295,73 +471,130 @@ pub enum SasError{
     Read,
     Cmd,
     SasProperty(String),
+    Io(std::io::Error),
+    Codec(String),
+    Truncated(String),
 }
 
-fn contains_bytes(bytes : &Vec<u8>, txt : &str) -> bool{
-    let txt_bytes = txt.as_bytes();
-    let txt_len = txt_bytes.len();
-    for i in 0..bytes.len()- txt_bytes.len(){
-        if &bytes[i..i + txt_len] == txt_bytes{
-            return true;
-        }
-    }
-    false
+/// Parts of a file skipped by a reader in salvage mode, see
+/// `ReadOptions::salvage`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Clone, Debug, Default)]
+pub struct DamageReport{
+    pub damaged : Vec<Damage>,
+    /// Rows of `row_count` that were not read. Final once `read_line`
+    /// returned false.
+    pub rows_lost : usize,
 }
 
-enum Encodings{
-    SingleByte(SingleByteEncoding),
-    MultiByte(UTF8Encoding),
+/// A damaged page, or a run of damaged rows on a page.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Clone, Debug)]
+pub struct Damage{
+    /// Index of the page, counting from the first page after the header.
+    pub page : usize,
+    /// Byte range in the file.
+    pub start : u64,
+    pub end : u64,
+    /// Number of skipped rows, None if a whole page could not be read.
+    pub rows_lost : Option<usize>,
+    pub error : String,
 }
 
-impl Encodings{
-    fn decode(&self, bytes : &[u8]) -> Result<String, SasError>{
-        match self{
-            Encodings::SingleByte(x) => {
-                match x.decode(bytes, DecoderTrap::Strict){
-                    Ok(val) => Ok(val),
-                    Err(_) => Err(SasError::ByteConversion),
-                }
-            }
-            Encodings::MultiByte(x) =>  {
-                match x.decode(bytes, DecoderTrap::Strict){
-                    Ok(val) => Ok(val),
-                    Err(_) => Err(SasError::ByteConversion),
-                }
-            }
+/// Options for reading a file. The file is opened by one of the methods
+/// taking the options, e.g. `ReadOptions::default().read(reader)`.
+#[derive(Clone, Debug)]
+pub struct ReadOptions{
+    pub(crate) default_encoding : Encoding,
+    pub(crate) encoding : Option<Encoding>,
+    pub(crate) decode_policy : DecodePolicy,
+    pub(crate) include_deleted : bool,
+    pub(crate) salvage : bool,
+}
+
+impl Default for ReadOptions{
+    fn default() -> ReadOptions{
+        ReadOptions{
+            default_encoding : Encoding::WLATIN1,
+            encoding : None,
+            decode_policy : DecodePolicy::Strict,
+            include_deleted : false,
+            salvage : false,
         }
     }
-    
-    fn new(decoder_num : usize) -> Option<Self>{
-        match decoder_num{
-            20 => Some(Encodings::MultiByte(UTF8Encoding)),
-            29 => Some(Encodings::SingleByte(*ISO_8859_1)),
-            30 => Some(Encodings::SingleByte(*ISO_8859_2)),
-            31 => Some(Encodings::SingleByte(*ISO_8859_3)),
-            32 => Some(Encodings::SingleByte(*ISO_8859_4)),
-            33 => Some(Encodings::SingleByte(*ISO_8859_5)),
-            34 => Some(Encodings::SingleByte(*ISO_8859_6)),
-            35 => Some(Encodings::SingleByte(*ISO_8859_7)),
-            36 => Some(Encodings::SingleByte(*ISO_8859_8)),
-            //37 => Some(Encodings::SingleByte(*ISO_8859_9)) not supported by library,
-            38 => Some(Encodings::SingleByte(*ISO_8859_10)),
-            //39 => Some(Encodings::SingleByte(*ISO_8859_11)) not supported by library,
-            40 => Some(Encodings::SingleByte(*ISO_8859_15)),
-            51 => Some(Encodings::SingleByte(*WINDOWS_874)),
-            60 => Some(Encodings::SingleByte(*WINDOWS_1250)),
-            61 => Some(Encodings::SingleByte(*WINDOWS_1251)),
-            62 => Some(Encodings::SingleByte(*WINDOWS_1252)),
-            63 => Some(Encodings::SingleByte(*WINDOWS_1253)),
-            64 => Some(Encodings::SingleByte(*WINDOWS_1254)),
-            65 => Some(Encodings::SingleByte(*WINDOWS_1255)),
-            66 => Some(Encodings::SingleByte(*WINDOWS_1256)),
-            67 => Some(Encodings::SingleByte(*WINDOWS_1257)),
-            68 => Some(Encodings::SingleByte(*WINDOWS_1258)),
-            _ => None,
-        }
+}
+
+impl ReadOptions{
+    /// Encoding used when the header does not name one (encoding id 0 or
+    /// `any`) or names an unknown id, see `SAS7bdat::encoding_id`. Defaults
+    /// to `wlatin1`.
+    pub fn default_encoding(mut self, encoding : Encoding) -> ReadOptions{
+        self.default_encoding = encoding;
+        self
+    }
+
+    /// Reads the file with `encoding`, whatever the header says.
+    pub fn encoding(mut self, encoding : Encoding) -> ReadOptions{
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// How character cells that fail to decode are read. Defaults to
+    /// `DecodePolicy::Strict`.
+    pub fn decode_policy(mut self, policy : DecodePolicy) -> ReadOptions{
+        self.decode_policy = policy;
+        self
+    }
+
+    /// Returns rows flagged as deleted instead of skipping them, see
+    /// `SAS7bdat::row_deleted`.
+    pub fn include_deleted(mut self, include : bool) -> ReadOptions{
+        self.include_deleted = include;
+        self
     }
+
+    /// Skips pages and rows that cannot be read instead of failing, see
+    /// `SAS7bdat::damage_report`. Without it a partial last page or fewer
+    /// rows than `row_count` fail with `SasError::Truncated`.
+    pub fn salvage(mut self, salvage : bool) -> ReadOptions{
+        self.salvage = salvage;
+        self
+    }
+
+    /// Reads a file from `reader`, see `SAS7bdat::from_reader`.
+    pub fn read<R : std::io::Read>(&self, reader : R) -> Result<SAS7bdat<R>, SasError>{
+        SAS7bdat::from_source(Source::Stream(BufReader::new(reader)), self)
+    }
+}
+
+//What the numbers of a column stand for, going by its format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Temporal{
+    Date,
+    DateTime,
+    Time,
 }
 
-impl<R: std::io::Read + std::io::Seek> Iterator for SAS7bdat<R>{
+//The date, datetime and time formats, by upper case name. The reader
+//decodes these columns as dates and times, and SasVal::format renders them.
+fn temporal(format : &str) -> Option<Temporal>{
+    match format{
+        "DATE" | "DDMMYY" | "MMDDYY" | "YYMMDD" | "E8601DA" | "MONYY" | "YEAR" => Some(Temporal::Date),
+        "DATETIME" | "E8601DT" => Some(Temporal::DateTime),
+        "TIME" | "HHMM" | "TOD" | "E8601TM" => Some(Temporal::Time),
+        _ => None,
+    }
+}
+
+fn permute<T : Clone>(vals : &[T], order : &[usize]) -> Vec<T>{
+    order.iter().map(|i| vals[*i].clone()).collect()
+}
+
+fn contains_bytes(bytes : &[u8], txt : &str) -> bool{
+    bytes.windows(txt.len()).any(|x| x == txt.as_bytes())
+}
+
+impl<R: std::io::Read> Iterator for SAS7bdat<R>{
     type Item = Result<Vec<SasVal>, SasError>;
 
     fn next(&mut self) -> Option<Self::Item>{
@@ -377,7 +610,50 @@ impl<R: std::io::Read + std::io::Seek> Iterator for SAS7bdat<R>{
     }
 }
 
+//Header and page length from the first 288 bytes of a file, for readers
+//that need the page layout before the header is parsed.
+fn header_layout(hdr : &[u8]) -> Result<(usize, usize), SasError>{
+    if hdr.len() < 288 || &hdr[0..MAGIC.len()] != MAGIC{
+        return Err(SasError::SasProperty("Magic Byte incorrect, SAS file?".to_string()));
+    }
+    let mut align1 = 0;
+    if hdr[ALIGN_2_OFFSET..ALIGN_2_OFFSET + ALIGN_2_LENGTH] == ALIGN_1_CHECKER_VALUE{
+        align1 = ALIGN_2_VALUE;
+    }
+    let int_at = |off : usize| {
+        let mut val = [0u8; 4];
+        val.copy_from_slice(&hdr[off + align1..off + align1 + 4]);
+        if hdr[ENDIANNESS_OFFSET] == b'\x01'{
+            u32::from_le_bytes(val) as usize
+        } else {
+            u32::from_be_bytes(val) as usize
+        }
+    };
+    Ok((int_at(HEADER_SIZE_OFFSET), int_at(PAGE_SIZE_OFFSET)))
+}
+
+//header_layout, failing on the lengths get_properties rejects
+fn checked_layout(hdr : &[u8]) -> Result<(usize, usize), SasError>{
+    let (hdr_len, page_len) = header_layout(hdr)?;
+    if !(288..=MAX_PAGE_LEN).contains(&hdr_len){
+        return Err(SasError::SasProperty(format!("Invalid header length : {hdr_len}")));
+    }
+    if !(MIN_PAGE_LEN..=MAX_PAGE_LEN).contains(&page_len){
+        return Err(SasError::SasProperty(format!("Invalid page length : {page_len}")));
+    }
+    Ok((hdr_len, page_len))
+}
+
 impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
+    pub fn new(mut reader : std::io::BufReader<R>) -> Result<SAS7bdat<R>, SasError> {
+        if reader.rewind().is_err(){
+            return Err(SasError::Read);
+        }
+        SAS7bdat::from_source(Source::Stream(reader), &ReadOptions::default())
+    }
+}
+
+impl<R : std::io::Read> SAS7bdat<R>{
 
     fn utf_8(&self, bytes : &[u8]) -> Result<String, SasError>{
         self.text_decoder.decode(bytes)
@@ -393,9 +669,9 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
 
     fn get_properties(&mut self) -> Result<(), SasError>{
         self.props = SasProperties::default();
-        self.read_bytes(0,288)?;
-        self.cached_page = vec![0;288];        
-        self.cached_page.copy_from_slice(&self.buf[0..288]);
+        if self.src.read_page(&mut self.cached_page, 288)? < 288 {
+            return Err(SasError::Read);
+        }
         if &self.cached_page[0..MAGIC.len()] != MAGIC{
             return Err(SasError::SasProperty("Magic Byte incorrect, SAS file?".to_string()));
         }
@@ -431,12 +707,14 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
         }
         self.read_bytes(ENCODING_OFFSET, ENCODING_LENGTH)?;
 
-        match Encodings::new(usize::from(self.buf[0])){
-            Some(x) => self.text_decoder = x,
-            None => {
-                eprintln!("Unknown/Unsupported encoding : {}", usize::from(self.buf[0]));
-                return Err(SasError::UnknownDecoder);
-            }
+        self.encoding_id = self.buf[0];
+        match (self.opts.encoding, self.encoding_id, Encoding::from_id(self.encoding_id)){
+            (Some(x), _, _) => self.text_decoder = x,
+            (None, _, Some(x)) if x.is_supported() => self.text_decoder = x,
+            (None, id, Some(x)) => return Err(SasError::SasProperty(format!(
+                "the encoding {x} (id {id}) cannot be decoded, read the file with ReadOptions::encoding"))),
+            //the id is still available through encoding_id
+            (None, _, None) => self.text_decoder = self.opts.default_encoding,
         }
 
         self.read_bytes(DATASET_OFFSET, DATASET_LENGTH)?;
@@ -444,25 +722,30 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
 
         self.read_bytes(FILE_TYPE_OFFSET, FILE_TYPE_LENGTH)?;
         self.file_type = self.utf_8(&self.buf[0..FILE_TYPE_LENGTH])?;
-        self.date_created = self.read_float(DATE_CREATED_OFFSET, DATE_CREATED_LENGTH);
-        self.date_modified = self.read_float(DATE_MODIFIED_OFFSET, DATE_MODIFIED_LENGTH);
+        self.date_created = self.read_float(DATE_CREATED_OFFSET + align1, DATE_CREATED_LENGTH)?;
+        self.date_modified = self.read_float(DATE_MODIFIED_OFFSET + align1, DATE_MODIFIED_LENGTH)?;
         self.props.hdr_len = self.read_int(HEADER_SIZE_OFFSET + align1, HEADER_SIZE_LENGTH)?;
 
         if self.u64 && self.props.hdr_len != 8192 {
             return Err(SasError::SasProperty("Inappropriate headerlength for 64bit architecture".to_string()));
         }
 
-        let mut tmp_buf : Vec<u8> = vec![0;self.props.hdr_len - 288]; 
-        if self.buf_rdr.read_exact(&mut tmp_buf[0..(self.props.hdr_len - 288)]).is_err(){
-            return Err(SasError::Read);
+        if !(288..=MAX_PAGE_LEN).contains(&self.props.hdr_len) {
+            return Err(SasError::SasProperty(format!("Invalid header length : {}", self.props.hdr_len)));
         }
-
-        self.cached_page.extend_from_slice(&tmp_buf);
-        if self.cached_page.len() != self.props.hdr_len{
+        if !self.src.extend_page(&mut self.cached_page, self.props.hdr_len - 288)?{
             return Err(SasError::SasProperty("Sas7BDAT file seems to be truncated".to_string()));
         }
-        self.props.page_count = self.read_int(PAGE_COUNT_OFFSET + align1, PAGE_COUNT_LENGTH)?;
+        //the page count takes 8 bytes in 64 bit files
+        let mut page_count_len = PAGE_COUNT_LENGTH;
+        if self.u64 {
+            page_count_len = self.props.int_len;
+        }
+        self.props.page_count = self.read_int(PAGE_COUNT_OFFSET + align1, page_count_len)?;
         self.props.page_len = self.read_int(PAGE_SIZE_OFFSET + align1, PAGE_SIZE_LENGTH)?;
+        if !(MIN_PAGE_LEN..=MAX_PAGE_LEN).contains(&self.props.page_len) {
+            return Err(SasError::SasProperty(format!("Invalid page length : {}", self.props.page_len)));
+        }
         self.read_bytes(SAS_RELEASE_OFFSET + total_align, SAS_RELEASE_LENGTH)?;
         self.sas_release = self.utf_8(&self.buf[0..SAS_RELEASE_LENGTH])?;
 
@@ -510,10 +793,15 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
     }
 
     fn get_sub_hdr_idx(&self, sig : Vec<u8>, compression: usize, ptype : usize) -> Result<usize, SasError> {
+        //a compressed row is not a subheader, even if it starts with the
+        //bytes of a signature
+        if !self.compression.is_empty() && compression == COMPRESSED_SUBHEADER_ID && ptype == COMPRESSED_SUBHEADER_TYPE {
+            return Ok(DATA_SUBHDR_IDX);
+        }
         match self.hdr_sig_map.get(&sig){
             Some(val) => Ok(*val),
             None => {
-                let f = (compression == COMPRESSED_SUBHEADER_ID) || (compression == 0);
+                let f = matches!(compression, 0 | COMPRESSED_SUBHEADER_ID | DELETED_SUBHEADER_ID);
                 if !self.compression.is_empty() && f && (ptype == COMPRESSED_SUBHEADER_TYPE){
                     Ok(DATA_SUBHDR_IDX)
                 } else {
@@ -522,7 +810,7 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
             }
         }
     }
-    fn process_row_size_sub_hdr(&mut self, off :usize, length : usize) -> Result<(), SasError>{
+    fn process_row_size_sub_hdr(&mut self, off :usize, _len : usize) -> Result<(), SasError>{
         let int_len = self.props.int_len;
         let mut lcs_off = off;
         let mut lcp_off = off;
@@ -543,7 +831,7 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
         Ok(())
     }
 
-    fn process_col_size_sub_hdr(&mut self, mut off : usize, len : usize) -> Result<(), SasError> {
+    fn process_col_size_sub_hdr(&mut self, mut off : usize, _len : usize) -> Result<(), SasError> {
         let int_len = self.props.int_len;
         off += int_len;
         self.props.col_cnt = self.read_int(off, int_len)?;
@@ -554,7 +842,7 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
         Ok(())
     }
 
-    fn process_col_txt_sub_hdr(&mut self, mut off : usize, len : usize) -> Result<(), SasError> {
+    fn process_col_txt_sub_hdr(&mut self, mut off : usize, _len : usize) -> Result<(), SasError> {
         off += self.props.int_len;
         //let txt_block_sz = len - self.props.int_len;
         let txt_block_sz = self.read_int(off, TEXT_BLOCK_SIZE_LENGTH)?;
@@ -628,7 +916,22 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
             Ok(()) 
         }
 
-        fn process_col_list_sub_hdr(&mut self, _off : usize, _len : usize) -> Result<(), SasError>{
+        fn process_col_list_sub_hdr(&mut self, off : usize, len : usize) -> Result<(), SasError>{
+            let mut cnt_off = COLUMN_LIST_COUNT_OFFSET_X86;
+            let mut val_off = COLUMN_LIST_VALUES_OFFSET_X86;
+            if self.u64 {
+                cnt_off = COLUMN_LIST_COUNT_OFFSET_X64;
+                val_off = COLUMN_LIST_VALUES_OFFSET_X64;
+            }
+            let cnt = self.read_int(off + cnt_off, COLUMN_LIST_VALUE_LENGTH)?;
+            if val_off + cnt * COLUMN_LIST_VALUE_LENGTH > len {
+                return Err(SasError::SasProperty(format!("column list of {cnt} values exceeds its subheader")));
+            }
+            self.col_list.clear();
+            for i in 0..cnt {
+                let val = self.read_signed_int(off + val_off + i * COLUMN_LIST_VALUE_LENGTH, COLUMN_LIST_VALUE_LENGTH)?;
+                self.col_list.push(val as i16);
+            }
             Ok(())
         }
 
@@ -672,48 +975,239 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
             let col_label = self.utf_8(&label_names[label_start .. label_start + label_len])?;
             let format_names = &self.col_name_strings[format_idx];
             let col_format = self.utf_8(&format_names[format_start..format_start + format_len])?;
-            let cur_col_number = self.cols.len(); 
-
-            let col = Col{
-                col_id: cur_col_number,
-                name: self.col_names[cur_col_number].clone(),
-                label: col_label.clone(),
-                fmt: col_format.clone(), 
-                ctype: self.col_types[cur_col_number],
-                len: self.col_data_lens[cur_col_number],
-            };
+            let width = self.read_u16(off + COLUMN_FORMAT_WIDTH_OFFSET + 3 * int_len);
+            let decimals = self.read_u16(off + COLUMN_FORMAT_DECIMALS_OFFSET + 3 * int_len);
+            self.col_format_specs.push(ColumnFormat::new(&col_format, width, decimals));
             self.col_labels.push(col_label);
             self.col_formats.push(col_format);
-            self.cols.push(col);
             Ok(())
         }
-        fn row_count(&self) -> usize {
+        pub fn name(&self) -> &str {
+            self.name.trim_end()
+        }
+        pub fn row_count(&self) -> usize {
             self.row_count
         }
-        fn col_names(&self) -> &Vec<String>{
+        /// When the file was created, as stamped in its header. `None` if
+        /// the stamp is missing or out of range.
+        pub fn date_created(&self) -> Option<DateTime<Utc>> {
+            self.header_date(self.date_created)
+        }
+        /// When the file was last modified, as stamped in its header.
+        pub fn date_modified(&self) -> Option<DateTime<Utc>> {
+            self.header_date(self.date_modified)
+        }
+        fn header_date(&self, secs : f64) -> Option<DateTime<Utc>> {
+            if !secs.is_finite() {
+                return None;
+            }
+            Duration::try_milliseconds((secs * 1000.0).round() as i64)
+                .and_then(|x| self.date_base.checked_add_signed(x))
+        }
+        pub fn col_names(&self) -> &Vec<String>{
             &self.col_names
         }
-        fn col_label(&self) -> &Vec<String>{
+        pub fn col_label(&self) -> &Vec<String>{
             &self.col_labels
         }
-        fn col_types(&self) -> &Vec<u16>{
-            &self.col_types
+        pub fn col_formats(&self) -> &Vec<String>{
+            &self.col_formats
         }
-        fn parse_metadata(&mut self) -> Result<(), SasError> {
-            loop {
-                if self.buf_rdr.read_exact(&mut self.cached_page).is_err(){
-                    return Err(SasError::Read);
+        /// Format of each column with its width and decimals, for
+        /// `SasVal::format`.
+        pub fn col_format_specs(&self) -> &Vec<ColumnFormat>{
+            &self.col_format_specs
+        }
+
+        /// Encoding of the text in the file.
+        pub fn encoding(&self) -> Encoding {
+            self.text_decoder
+        }
+
+        /// Encoding id stored in the header, 0 if the file does not name
+        /// an encoding. `Encoding::from_id` returns `None` for the ids read
+        /// with `ReadOptions::default_encoding`.
+        pub fn encoding_id(&self) -> u8 {
+            self.encoding_id
+        }
+
+        /// Guesses the encoding of the character cells from the next
+        /// `max_rows` rows. Candidates are UTF-8, wlatin1, latin1 and the
+        /// encoding of the header. The sampled rows are used up, so the file
+        /// has to be opened again, possibly with `ReadOptions::encoding`, to
+        /// read all of it.
+        pub fn detect_encoding(&mut self, max_rows : usize) -> Result<EncodingGuess, SasError> {
+            let policy = self.opts.decode_policy;
+            self.opts.decode_policy = DecodePolicy::Raw;
+            let mut cells = Vec::new();
+            for _ in 0..max_rows {
+                match self.read_line() {
+                    Ok(true) => (),
+                    Ok(false) => break,
+                    Err(er) => {
+                        self.opts.decode_policy = policy;
+                        return Err(er);
+                    }
                 }
-                match self.process_page_meta() {
-                    Ok(done) => if done {
-                        break;
+                for val in self.row_vals.iter() {
+                    if let SasVal::Bytes(x) = val {
+                        cells.push(x.clone());
                     }
-                    Err(val) => return Err(val),
+                }
+            }
+            self.opts.decode_policy = policy;
+            let cells : Vec<&[u8]> = cells.iter().map(|x| x.as_slice()).collect();
+            let header = match Encoding::from_id(self.encoding_id) {
+                Some(x) if x.is_supported() => x,
+                _ => self.opts.default_encoding,
+            };
+            Ok(encodings::guess_encoding(&cells, header))
+        }
+
+        /// True if the row last read is flagged as deleted. Only happens
+        /// with `ReadOptions::include_deleted`.
+        pub fn row_deleted(&self) -> bool {
+            self.cur_row_deleted
+        }
+
+        /// Number of rows flagged as deleted passed so far.
+        pub fn deleted_count(&self) -> usize {
+            self.deleted_count
+        }
+
+        /// Pages and rows skipped so far in salvage mode, see
+        /// `ReadOptions::salvage`.
+        pub fn damage_report(&self) -> &DamageReport {
+            &self.damage
+        }
+
+        /// Entries of the subheader-counts subheader, empty if the file has
+        /// none.
+        pub fn subheader_counts(&self) -> &[SubHdrCount] {
+            &self.sub_hdr_counts
+        }
+
+        /// Number of cells per column that failed to decode so far, see
+        /// `DecodePolicy`.
+        pub fn decode_errors(&self) -> &[usize] {
+            &self.decode_errors
+        }
+        fn parse_metadata(&mut self) -> Result<(), SasError> {
+            loop {
+                let n = self.src.read_page(&mut self.cached_page, self.props.page_len)?;
+                if self.meta_page_read(n)? {
+                    break;
                 }
             };
+            self.end_metadata()
+        }
+
+        //Processes the n bytes just read into cached_page while parsing the
+        //metadata. Returns true once the metadata is complete. Shared by all
+        //readers, which only differ in how they fill the page.
+        fn meta_page_read(&mut self, n : usize) -> Result<bool, SasError> {
+            self.meta_page_read_with(n, Self::process_sub_hdr_at)
+        }
+
+        //meta_page_read with the subheaders of the page handed to sub_hdr by
+        //index, which lets the validator go on after a bad one.
+        fn meta_page_read_with<F>(&mut self, n : usize, mut sub_hdr : F) -> Result<bool, SasError>
+        where F : FnMut(&mut Self, usize) -> Result<(), SasError> {
+            //a file without rows may end after its metadata pages
+            if n == 0 && self.pages_read > 0 {
+                return Ok(true);
+            }
+            if n < self.props.page_len {
+                return Err(SasError::Truncated("file ends in the metadata pages".to_string()));
+            }
+            self.pages_read += 1;
+            self.read_page_hdr()?;
+            if self.is_page_metamix_amd(self.cur_page_type){
+                for i in 0..self.cur_page_sub_hdr_count{
+                    sub_hdr(self, i)?;
+                }
+            }
+            Ok(self.is_page_mix_data_type(self.cur_page_type) || !self.cur_page_data_sub_hdr_pointers.is_empty())
+        }
+
+        //Checks that the column subheaders describe the same columns and puts
+        //the columns in the order of the column list.
+        fn end_metadata(&mut self) -> Result<(), SasError> {
+            let cnt = self.props.col_cnt;
+            if self.col_names.len() != cnt || self.col_data_off.len() != cnt || self.col_formats.len() != cnt {
+                return Err(SasError::SasProperty(format!(
+                    "column subheaders do not line up : {cnt} columns, but {} names, {} attributes and {} formats",
+                    self.col_names.len(), self.col_data_off.len(), self.col_formats.len())));
+            }
+            if let Some(order) = self.col_order() {
+                self.col_names = permute(&self.col_names, &order);
+                self.col_labels = permute(&self.col_labels, &order);
+                self.col_formats = permute(&self.col_formats, &order);
+                self.col_format_specs = permute(&self.col_format_specs, &order);
+                self.col_types = permute(&self.col_types, &order);
+                self.col_data_off = permute(&self.col_data_off, &order);
+                self.col_data_lens = permute(&self.col_data_lens, &order);
+            }
+            self.plan = self.decode_plan()?;
+            self.row_vals = vec![SasVal::Numeric(0.0);cnt];
+            self.decode_errors = vec![0;cnt];
             Ok(())
         }
 
+        //The plan stops at the first column without bytes, the columns after
+        //it are not decoded.
+        fn decode_plan(&self) -> Result<Vec<ColPlan>, SasError> {
+            let mut plan = Vec::with_capacity(self.props.col_cnt);
+            for j in 0..self.props.col_cnt {
+                let len = self.col_data_lens[j];
+                if len == 0 {
+                    break;
+                }
+                let op = if self.col_types[j] == SAS_NUM_TYPE {
+                    let num = match (&self.byte_order, len) {
+                        (_, 9..) => return Err(SasError::SasProperty(format!(
+                            "numeric column {} has {len} bytes", self.col_names[j]))),
+                        (Endian::Little, 8) => NumOp::Le,
+                        (Endian::Big, 8) => NumOp::Be,
+                        (Endian::Little, _) => NumOp::ShortLe,
+                        (Endian::Big, _) => NumOp::ShortBe,
+                    };
+                    match temporal(&self.col_format_specs[j].name) {
+                        Some(Temporal::Date) => CellOp::Date(num),
+                        Some(Temporal::DateTime) => CellOp::DateTime(num),
+                        Some(Temporal::Time) => CellOp::Time(num),
+                        None => CellOp::Num(num),
+                    }
+                } else {
+                    CellOp::Text
+                };
+                plan.push(ColPlan{off : self.col_data_off[j], len, op});
+            }
+            Ok(plan)
+        }
+
+        //Column indices in the order SAS shows them. The column list is only
+        //used when it names every column once, its other values are not
+        //understood.
+        fn col_order(&self) -> Option<Vec<usize>> {
+            let cnt = self.props.col_cnt;
+            let order : Vec<usize> = self.col_list.iter()
+                .filter(|x| **x != 0)
+                .map(|x| usize::from(x.unsigned_abs()) - 1)
+                .collect();
+            let mut seen = vec![false; cnt];
+            if order.len() != cnt {
+                return None;
+            }
+            for i in order.iter() {
+                if *i >= cnt || seen[*i] {
+                    return None;
+                }
+                seen[*i] = true;
+            }
+            Some(order)
+        }
+
         fn is_page_metamix_amd(&self, page_type : isize) -> bool{
             matches!(page_type, PAGE_META_TYPE | 512 | 640 | PAGE_AMD_TYPE)
         }
@@ -730,15 +1224,28 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
             !matches!(cur_page, PAGE_META_TYPE | PAGE_DATA_TYPE | 512 | 640)
         }
 
-        fn process_page_meta(&mut self) -> Result<bool, SasError> {
-            self.read_page_hdr()?;
-            if self.is_page_metamix_amd(self.cur_page_type){
-                self.process_page_metadata()?;
+        fn process_sub_hdr_counts(&mut self, off : usize, len : usize) -> Result<(), SasError>{
+            let int_len = self.props.int_len;
+            let vec_len = SUBHEADER_COUNTS_VECTOR_LENGTH_MULTIPLIER * int_len;
+            self.sub_hdr_counts.clear();
+            for i in 0..SUBHEADER_COUNTS_VECTORS {
+                let vec_off = off + SUBHEADER_COUNTS_VECTORS_OFFSET_MULTIPLIER * int_len + i * vec_len;
+                if vec_off + vec_len > off + len {
+                    break;
+                }
+                let signature = self.read_sub_hdr_sig(vec_off)?;
+                if signature.iter().all(|x| *x == 0) {
+                    continue;
+                }
+                let count = SubHdrCount{
+                    signature,
+                    first_page : self.read_int(vec_off + int_len, int_len)?,
+                    first_pos : self.read_int(vec_off + 2 * int_len, SUBHEADER_COUNTS_POS_LENGTH)?,
+                    last_page : self.read_int(vec_off + 3 * int_len, int_len)?,
+                    last_pos : self.read_int(vec_off + 4 * int_len, SUBHEADER_COUNTS_POS_LENGTH)?,
+                };
+                self.sub_hdr_counts.push(count);
             }
-            Ok(self.is_page_mix_data_type(self.cur_page_type) || !self.cur_page_data_sub_hdr_pointers.is_empty())
-        }
-
-        fn process_sub_hdr_counts(&self, _off : usize, _len : usize) -> Result<(), SasError>{
             Ok(())
         }
 
@@ -764,19 +1271,23 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
         }
 
         fn process_page_metadata(&mut self) -> Result<(), SasError>{
-            let bit_off = self.props.page_bit_off;
             for i in 0..self.cur_page_sub_hdr_count{
-                let ptr = self.process_sub_hdr_ptrs(SUBHEADER_POINTERS_OFFSET + bit_off, i)?;
-                if ptr.len == 0 || ptr.compression == TRUNCATED_SUBHEADER_ID {
-                    continue;
-                }
-                let sub_hdr_sig = self.read_sub_hdr_sig(ptr.off)?;
-                let sub_hdr_idx = self.get_sub_hdr_idx(sub_hdr_sig, ptr.compression, ptr.ptype)?;
-                self.process_sub_hdr(sub_hdr_idx, ptr)?;
+                self.process_sub_hdr_at(i)?;
             }
             Ok(())
         }
 
+        //Processes the subheader of the pointer with index i.
+        fn process_sub_hdr_at(&mut self, i : usize) -> Result<(), SasError>{
+            let ptr = self.process_sub_hdr_ptrs(SUBHEADER_POINTERS_OFFSET + self.props.page_bit_off, i)?;
+            if ptr.len == 0 || ptr.compression == TRUNCATED_SUBHEADER_ID {
+                return Ok(());
+            }
+            let sub_hdr_sig = self.read_sub_hdr_sig(ptr.off)?;
+            let sub_hdr_idx = self.get_sub_hdr_idx(sub_hdr_sig, ptr.compression, ptr.ptype)?;
+            self.process_sub_hdr(sub_hdr_idx, ptr)
+        }
+
         fn ensure_buf_len(&mut self, len : usize){
             if self.buf.len() < len {
                 self.buf.resize(2 * len, 0);
@@ -785,25 +1296,16 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
 
         fn read_bytes(&mut self, off : usize, len : usize) -> Result<(), SasError> {
             self.ensure_buf_len(len);
-            if self.cached_page.is_empty(){
-                self.buf_rdr.seek(SeekFrom::Start(off.try_into().unwrap())).expect("Failed to seek file");
-                if self.buf_rdr.read_exact(&mut self.buf[0..len]).is_err(){
-                    return Err(SasError::Read);
-                }
-            } else {
-                if off + len > self.cached_page.len(){
-                    return Err(SasError::Read);
-                }
-                self.buf[0..len].copy_from_slice(&self.cached_page[off..(off+len)]);
+            if off + len > self.cached_page.len(){
+                return Err(SasError::Read);
             }
+            self.buf[0..len].copy_from_slice(&self.cached_page[off..(off+len)]);
             Ok(())
         }
 
-        fn read_float(&self, off : usize, w : usize) -> f64{
-            match self.byte_order {
-                Endian::Big => f64::from_be_bytes(self.buf[off..(off + w)].try_into().unwrap()),
-                Endian::Little => f64::from_le_bytes(self.buf[off..(off + w)].try_into().unwrap()),
-            }
+        fn read_float(&mut self, off : usize, w : usize) -> Result<f64, SasError>{
+            self.read_bytes(off, w)?;
+            Ok(f64::from_bytes(&self.buf, 0, w, &self.byte_order))
         }
 
         fn read_int_from_buf(&self, w : usize) -> Result<usize, SasError> {
@@ -833,17 +1335,19 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
             self.read_int_from_buf(w)
         }
 
+        //Unsigned 16 bit field, 0 if it is beyond the page.
+        fn read_u16(&mut self, off : usize) -> u16 {
+            match self.read_bytes(off, 2){
+                Ok(()) => i16::from_bytes(&self.buf, 0, 2, &self.byte_order) as u16,
+                Err(_) => 0,
+            }
+        }
+
         fn read_signed_int_from_buf(&self, w : usize) -> Result<isize, SasError> {
 
             match w {
-                1 => match (i8::from_bytes(&self.buf, 0, 1, &self.byte_order)).try_into(){
-                    Ok(val) => Ok(val),
-                    Err(_) => Err(SasError::TypeConversion),
-                }
-                2 => match (i16::from_bytes(&self.buf, 0, 2, &self.byte_order)).try_into() {
-                    Ok(val) => Ok(val),
-                    Err(_) => Err(SasError::TypeConversion),
-                }
+                1 => Ok(isize::from(i8::from_bytes(&self.buf, 0, 1, &self.byte_order))),
+                2 => Ok(isize::from(i16::from_bytes(&self.buf, 0, 2, &self.byte_order))),
                 4 => match (i32::from_bytes(&self.buf, 0, 4, &self.byte_order)).try_into(){
                     Ok(val) => Ok(val),
                     Err(_) => Err(SasError::TypeConversion),
@@ -862,80 +1366,77 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
         }
 
         fn process_byte_array_with_data(&mut self, off : usize, len : usize) -> Result<(), SasError>{
-            let mut src : Vec<u8> = Vec::new();
+            if off + len > self.cached_page.len() {
+                return Err(SasError::SasProperty("Row exceeds page boundary".to_string()));
+            }
             if !self.compression.is_empty() && len < self.props.row_len {
                 let decomp = self.get_decompressor();
                 match decomp {
                     Some(f) => {
-                        match f(self.props.row_len, &self.cached_page[off .. off + len]) {
-                            Ok(vec) => {
-                                src = vec;
-                            }
-                            Err(val) => return Err(val),
-                        }
+                        //one buffer serves every row
+                        self.row_buf.resize(self.props.row_len, 0);
+                        f(&self.cached_page[off .. off + len], &mut self.row_buf)?;
                     }
                     None => {
                         return Err(SasError::SasProperty("Compressor specified, but not found".to_string()));
                     }
                 }
+                self.cur_row = None;
             } else {
-                if off + len > self.cached_page.len() {
-                    let old_page = self.cached_page.clone();
-                    match self.read_next_page() {
-                        Ok(true) => self.cached_page.extend_from_slice(&old_page),
-                        Ok(false) => return Err(SasError::SasProperty("Error reading next page!".to_string())),
-                        Err(_) => return Err(SasError::SasProperty("Error reading next page!".to_string())),
-                    }
-                }
-                src = self.cached_page[off .. off + len].to_vec();
+                self.cur_row = Some((off, len));
+            }
+            //Uncompressed rows are decoded straight from the page.
+            let src : &[u8] = match self.cur_row {
+                Some((off, len)) => &self.cached_page[off .. off + len],
+                None => &self.row_buf,
             };
 
-            for j in 0..self.props.col_cnt{
-                let len = self.col_data_lens[j];
-                if len == 0{
-                    break;
-                }
-
-                let start = self.col_data_off[j];
-                let end = start + len;
-                let tmp = &src[start..end];
-                //Handle numeric types: 
-                if self.cols[j].ctype == SAS_NUM_TYPE {
-                    //First perform float conversion
-                    self.float_buf = [0;8];
-                    let num = 
-                        match self.byte_order {
-                            Endian::Little => {
-                                let m = 8 - len;
-                                self.float_buf[m .. 8].copy_from_slice(tmp);
-                                f64::from_le_bytes(self.float_buf)
+            if let Some(batch) = self.batch.as_mut(){
+                let text = TextOpts{decoder : self.text_decoder, policy : self.opts.decode_policy, trim : self.trim_strings};
+                batch.push_row(&self.plan, src, &text, &mut self.decode_errors)?;
+            } else {
+                for (j, col) in self.plan.iter().enumerate(){
+                    let bytes = match src.get(col.off..col.off + col.len){
+                        Some(val) => val,
+                        None => return Err(SasError::BufLen),
+                    };
+                    self.row_vals[j] = match col.op{
+                        CellOp::Num(num) => SasVal::Numeric(read_num(num, bytes)),
+                        //missing values and values beyond the chrono range
+                        //stay numeric
+                        CellOp::Date(num) => {
+                            let x = read_num(num, bytes);
+                            match Duration::try_days(x as i64).and_then(|d| self.date_base.checked_add_signed(d)){
+                                Some(val) if !x.is_nan() => SasVal::Date(val.date_naive()),
+                                _ => SasVal::Numeric(x),
                             }
-                            Endian::Big => {
-                                self.float_buf[0 .. len].copy_from_slice(tmp);
-                                f64::from_be_bytes(self.float_buf)
+                        }
+                        CellOp::DateTime(num) => {
+                            let x = read_num(num, bytes);
+                            match Duration::try_seconds(x as i64).and_then(|d| self.date_base.checked_add_signed(d)){
+                                Some(val) if !x.is_nan() => SasVal::DateTime(val),
+                                _ => SasVal::Numeric(x),
                             }
-                        };
-
-                    //Check which kind of datetype is given, then convert according 
-                    //to definition.
-                    if self.col_formats[j] == "DATE"|| self.col_formats[j] == "DDMMYY"{
-                        let new = self.date_base + Duration::days(num as i64);
-                        self.row_vals[j] = SasVal::Date(new.date_naive());
-                    } else if self.col_formats[j] == "DATETIME"{
-                        let new = self.date_base + Duration::seconds(num as i64);
-                        self.row_vals[j] = SasVal::DateTime(new);
-                    } else {
-                        self.row_vals[j] = SasVal::Numeric(num);
-                    }
-
-                    //Handle String types
-                } else {
-                    let mut st = self.utf_8(tmp)?;
-                    if self.trim_strings{
-                        st = st.trim_end_matches(&['\u{0000}', '\u{0020}']).to_string();
-                    }
-                    self.row_vals[j] = SasVal::Text(st);
-                    //println!("{st}");
+                        }
+                        //durations of a day or more stay numeric
+                        CellOp::Time(num) => {
+                            let secs = read_num(num, bytes);
+                            match NaiveTime::from_num_seconds_from_midnight_opt(secs as u32, 0){
+                                Some(val) if (0.0..86400.0).contains(&secs) => SasVal::Time(val),
+                                _ => SasVal::Numeric(secs),
+                            }
+                        }
+                        //the policy is not part of the plan, detect_encoding
+                        //switches it to read the raw bytes
+                        CellOp::Text if self.opts.decode_policy == DecodePolicy::Raw => SasVal::Bytes(bytes.to_vec()),
+                        CellOp::Text => {
+                            let mut st = decode_text(&self.text_decoder, self.opts.decode_policy, &mut self.decode_errors[j], bytes)?;
+                            if self.trim_strings{
+                                st = st.trim_end_matches(['\u{0000}', '\u{0020}']).to_string();
+                            }
+                            SasVal::Text(st)
+                        }
+                    };
                 }
             }
 
@@ -947,114 +1448,286 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
 
         fn read_page_hdr(&mut self) -> Result<(), SasError>{
             let bit_off = self.props.page_bit_off;
-            self.cached_page.len();
             self.cur_page_block_count = self.read_int(BLOCK_COUNT_OFFSET + bit_off, BLOCK_COUNT_LENGTH)?;
             self.cur_page_sub_hdr_count = self.read_int(SUBHEADER_COUNT_OFFSET + bit_off, SUBHEADER_COUNT_LENGTH)?;
             //TODO factor out read_signed_int
             self.cur_page_type = self.read_signed_int(PAGE_TYPE_OFFSET + bit_off, PAGE_TYPE_LENGTH)?;
+            //Data and mix pages with deleted rows are flagged and carry a
+            //bitmap of the deleted rows.
+            self.cur_page_has_deleted = matches!(self.cur_page_type, 384 | 640);
+            if self.cur_page_has_deleted {
+                self.cur_page_type &= !PAGE_DELETED_FLAG;
+            }
             Ok(())
         }
 
         fn read_next_page(&mut self) -> Result<bool, SasError>{
-            self.cur_page_data_sub_hdr_pointers = Vec::with_capacity(10);
-            self.cached_page = vec![0;self.props.page_len];
-            let n = self.buf_rdr.read_exact(&mut self.cached_page);
-            match n {
-                Ok(()) => (),
-                //number of red bytes smaller than cached_page length
-                Err(er) if er.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    return Ok(true);
+            let n = self.src.read_page(&mut self.cached_page, self.props.page_len)?;
+            self.data_page_read(n)
+        }
+
+        //Processes the n bytes just read into cached_page while reading rows.
+        //Returns true at the end of the file.
+        fn data_page_read(&mut self, n : usize) -> Result<bool, SasError>{
+            if n < self.props.page_len {
+                self.end_of_pages(n)?;
+                return Ok(true);
+            }
+            self.start_page()?;
+            Ok(false)
+        }
+
+        //File range of the page with index idx.
+        fn page_range(&self, idx : usize) -> (u64, u64) {
+            let start = (self.props.hdr_len + idx * self.props.page_len) as u64;
+            (start, start + self.props.page_len as u64)
+        }
+
+        fn add_damage(&mut self, start : u64, end : u64, rows_lost : Option<usize>, er : &SasError) {
+            let page = self.pages_read.saturating_sub(1);
+            let error = format!("{er:?}");
+            //consecutive bad rows with the same error are merged
+            if let Some(last) = self.damage.damaged.last_mut() {
+                if let (true, Some(x), Some(y)) = (last.page == page && last.end == start && last.error == error, last.rows_lost, rows_lost) {
+                    last.end = end;
+                    last.rows_lost = Some(x + y);
+                    return;
+                }
+            }
+            self.damage.damaged.push(Damage{page, start, end, rows_lost, error});
+        }
+
+        //Called once the file has no full page left, n bytes of a partial
+        //page were read. Checks that all rows were read.
+        fn end_of_pages(&mut self, n : usize) -> Result<(), SasError> {
+            if self.eof {
+                return Ok(());
+            }
+            self.eof = true;
+            if n > 0 {
+                let msg = format!("the last page has {n} of {} bytes", self.props.page_len);
+                if !self.opts.salvage {
+                    return Err(SasError::Truncated(msg));
                 }
-                Err(_) => return Err(SasError::Read),
+                self.pages_read += 1;
+                let (start, _) = self.page_range(self.pages_read - 1);
+                self.add_damage(start, start + n as u64, None, &SasError::Truncated(msg));
+            }
+            let seen = self.cur_row_in_file_idx;
+            if self.opts.salvage {
+                self.damage.rows_lost = self.row_count.saturating_sub(seen - self.damaged_rows);
+                return Ok(());
             }
+            //row_count may or may not include the deleted rows
+            if seen == self.row_count || seen - self.deleted_count == self.row_count {
+                Ok(())
+            } else if seen < self.row_count {
+                Err(SasError::Truncated(format!("read {seen} of {} rows", self.row_count)))
+            } else {
+                Err(SasError::SasProperty(format!("read {seen} rows, but the file has {}", self.row_count)))
+            }
+        }
+
+        //Like process_next_page, but in salvage mode a page that cannot be
+        //read is recorded and yields no rows.
+        fn start_page(&mut self) -> Result<(), SasError> {
+            let er = match self.process_next_page() {
+                Ok(()) if self.opts.salvage && !self.is_known_page_type(self.cur_page_type) => {
+                    SasError::SasProperty(format!("unknown page type : {}", self.cur_page_type))
+                }
+                Ok(()) => return Ok(()),
+                Err(er) if self.opts.salvage => er,
+                Err(er) => return Err(er),
+            };
+            let (start, end) = self.page_range(self.pages_read - 1);
+            self.add_damage(start, end, None, &er);
+            self.cur_page_data_sub_hdr_pointers.clear();
+            self.cur_page_type = -1;
+            Ok(())
+        }
 
+        fn is_known_page_type(&self, page_type : isize) -> bool {
+            matches!(page_type, PAGE_META_TYPE | PAGE_DATA_TYPE | 512 | PAGE_AMD_TYPE | 16384 | -28672)
+        }
+
+        //Prepares the page in cached_page for reading its rows. Pages of other
+        //types have no rows and are skipped by read_line.
+        fn process_next_page(&mut self) -> Result<(), SasError>{
+            self.pages_read += 1;
+            self.cur_page_data_sub_hdr_pointers = Vec::with_capacity(10);
+            self.cur_row_on_page_idx = 0;
             self.read_page_hdr()?;
             if self.cur_page_type == PAGE_META_TYPE {
                 self.process_page_metadata()?;
             }
+            Ok(())
+        }
+
+        fn page_exhausted(&self) -> bool {
+            self.cur_row_on_page_idx >= self.cur_page_row_count()
+        }
+
+        fn cur_page_row_count(&self) -> usize {
             if self.check_page_type(self.cur_page_type) {
-                return self.read_next_page();
+                0
+            } else if self.cur_page_type == PAGE_META_TYPE {
+                self.cur_page_data_sub_hdr_pointers.len()
+            } else if self.is_page_mix_type(self.cur_page_type) {
+                cmp::min(self.row_count, self.props.mix_page_row_cnt)
+            } else {
+                self.cur_page_block_count
             }
-            Ok(false)
         }
 
-        pub fn read_line(&mut self) -> Result<bool, SasError> {
-            let bit_off = self.props.page_bit_off;
-            let sub_hdr_ptr_len = self.props.sub_hdr_ptr_len;
+        fn mix_align_corr(&self) -> usize {
+            if self.no_align_correction {
+                return 0;
+            }
+            (self.props.page_bit_off + SUBHEADER_POINTERS_OFFSET +
+                self.cur_page_sub_hdr_count * self.props.sub_hdr_ptr_len) % 8
+        }
 
-            if self.cached_page.is_empty() {
-                self.buf_rdr.seek(SeekFrom::Start(self.props.hdr_len.try_into().unwrap())).expect("Could not read page!");
-                self.read_next_page()?;
+        fn cur_row_is_deleted(&mut self) -> Result<bool, SasError> {
+            if self.cur_page_type == PAGE_META_TYPE {
+                let ptr = &self.cur_page_data_sub_hdr_pointers[self.cur_row_on_page_idx];
+                return Ok(ptr.compression == DELETED_SUBHEADER_ID);
+            }
+            if !self.cur_page_has_deleted {
+                return Ok(false);
             }
+            let mut ptr_off = PAGE_DELETED_POINTER_OFFSET_X86;
+            if self.u64 {
+                ptr_off = PAGE_DELETED_POINTER_OFFSET_X64;
+            }
+            let mut align_corr = 0;
+            if self.is_page_mix_type(self.cur_page_type) {
+                align_corr = self.mix_align_corr();
+            }
+            //The bitmap follows the rows, one bit per row, first row in the
+            //highest bit.
+            let rows = self.cur_page_block_count.saturating_sub(self.cur_page_sub_hdr_count);
+            let map_off = self.props.page_bit_off + self.read_int(ptr_off, PAGE_DELETED_POINTER_LENGTH)? +
+                align_corr + self.cur_page_sub_hdr_count * self.props.sub_hdr_ptr_len +
+                rows * self.props.row_len;
+            let idx = self.cur_row_on_page_idx;
+            self.read_bytes(map_off + idx / 8, 1)?;
+            Ok(self.buf[0] & (0x80 >> (idx % 8)) != 0)
+        }
 
+        fn skip_row(&mut self) {
+            self.cur_row_on_page_idx += 1;
+            self.cur_row_in_chunk_idx += 1;
+            self.cur_row_in_file_idx += 1;
+        }
+
+        /// Reads the next row into `row_vals`. Rows flagged as deleted are
+        /// skipped unless `ReadOptions::include_deleted` is set.
+        pub fn read_line(&mut self) -> Result<bool, SasError> {
+            //Pages are only ever read forward, and only once the current
+            //one has been used up, so no seeking is needed.
             loop {
-                if self.cur_page_type == PAGE_META_TYPE {
-                    if self.cur_row_on_page_idx >= self.cur_page_data_sub_hdr_pointers.len() {
-                        match self.read_next_page(){
-                            Ok(true) => return Ok(false),
-                            Ok(false) =>{ 
-                                self.cur_row_on_page_idx = 0;
-                                continue;
-                            }
-                            Err(val) => return Err(val),
-                        }
-                    }
-                    let cur_sub_hdr_ptr = &self.cur_page_data_sub_hdr_pointers[self.cur_row_on_page_idx];
-                    match self.process_byte_array_with_data(cur_sub_hdr_ptr.off, cur_sub_hdr_ptr.len){
-                        Ok(()) => return Ok(true),
-                        Err(val) => return Err(val),
-                    }
-                } else if self.is_page_mix_type(self.cur_page_type) {
-                    let mut align_corr = bit_off + SUBHEADER_POINTERS_OFFSET +
-                        self.cur_page_sub_hdr_count * sub_hdr_ptr_len % 8;
-                    if self.no_align_correction {
-                        align_corr = 0;
-                    }
-                    let off = bit_off + SUBHEADER_POINTERS_OFFSET + 
-                        self.cur_page_sub_hdr_count * sub_hdr_ptr_len +
-                        self.cur_row_on_page_idx * self.props.row_len +
-                        align_corr;
-                    if self.process_byte_array_with_data(off, self.props.row_len).is_err() {
-                        return Err(SasError::SasProperty("Could not process byte array".to_string()));
-                    }
-                    if self.cur_row_on_page_idx == cmp::min(self.row_count, self.props.mix_page_row_cnt){
-                        match self.read_next_page(){
-                            Ok(true) => return Ok(false),
-                            Err(val) => return Err(val),
-                            Ok(false) => {
-                                self.cur_row_on_page_idx = 0;
-                            }
-                        }
+                while self.page_exhausted() {
+                    if self.read_next_page()? {
+                        return Ok(false);
                     }
+                }
+                if self.read_row_on_page()? {
                     return Ok(true);
-                } else if self.cur_page_type == PAGE_DATA_TYPE {
-                    if self.process_byte_array_with_data(
-                        bit_off + SUBHEADER_POINTERS_OFFSET + self.cur_row_on_page_idx * self.props.row_len,
-                        self.props.row_len).is_err(){
-                        return Err(SasError::SasProperty("Could not process byte array".to_string()));
+                }
+            }
+        }
+
+        //Reads the next row of the current page, skipping deleted rows and,
+        //in salvage mode, damaged ones. Returns false once the page is used
+        //up.
+        fn read_row_on_page(&mut self) -> Result<bool, SasError> {
+            while !self.page_exhausted() {
+                let res = self.cur_row_is_deleted().and_then(|deleted| {
+                    self.cur_row_deleted = deleted;
+                    if deleted {
+                        self.deleted_count += 1;
                     }
-                    if self.cur_row_on_page_idx == self.cur_page_block_count {
-                        match self.read_next_page(){
-                            Ok(true) => return Ok(false),
-                            Err(val) => return Err(val),
-                            Ok(false) => {
-                                self.cur_row_on_page_idx = 0;
-                            }
-                        }
+                    if deleted && !self.opts.include_deleted {
+                        return Ok(false);
                     }
-                    return Ok(true);
-                } else {
-                    return Err(SasError::SasProperty(format!("unknown page type : {}", self.cur_page_type)));
+                    self.read_row()
+                });
+                match res {
+                    Ok(true) => return Ok(true),
+                    Ok(false) => self.skip_row(),
+                    Err(er) if self.opts.salvage => {
+                        let (off, len) = self.cur_row_span();
+                        let (start, _) = self.page_range(self.pages_read - 1);
+                        self.add_damage(start + off as u64, start + (off + len) as u64, Some(1), &er);
+                        self.damaged_rows += 1;
+                        self.damage.rows_lost += 1;
+                        self.skip_row();
+                    }
+                    Err(er) => return Err(er),
                 }
             }
+            Ok(false)
+        }
+
+        //Offset and length of the current row in the page.
+        fn cur_row_span(&self) -> (usize, usize) {
+            let bit_off = self.props.page_bit_off;
+            if self.cur_page_type == PAGE_META_TYPE {
+                let ptr = &self.cur_page_data_sub_hdr_pointers[self.cur_row_on_page_idx];
+                (ptr.off, ptr.len)
+            } else if self.is_page_mix_type(self.cur_page_type) {
+                let off = bit_off + SUBHEADER_POINTERS_OFFSET +
+                    self.cur_page_sub_hdr_count * self.props.sub_hdr_ptr_len +
+                    self.cur_row_on_page_idx * self.props.row_len +
+                    self.mix_align_corr();
+                (off, self.props.row_len)
+            } else {
+                (bit_off + SUBHEADER_POINTERS_OFFSET + self.cur_row_on_page_idx * self.props.row_len, self.props.row_len)
+            }
+        }
+
+        fn read_row(&mut self) -> Result<bool, SasError> {
+            if self.check_page_type(self.cur_page_type) {
+                return Err(SasError::SasProperty(format!("unknown page type : {}", self.cur_page_type)));
+            }
+            let (off, len) = self.cur_row_span();
+            self.process_byte_array_with_data(off, len)?;
+            Ok(true)
         }
 
-        pub fn new(reader : std::io::BufReader<R>) -> Result<SAS7bdat<R>, SasError> {
+        /// Reads a SAS7bdat file from a reader that cannot seek, e.g. stdin,
+        /// a pipe or a decompressor. The header is buffered and the pages are
+        /// then read strictly forward.
+        pub fn from_reader(reader : R) -> Result<SAS7bdat<R>, SasError> {
+            ReadOptions::default().read(reader)
+        }
+
+        /// Raw bytes of the row last read by `read_line`, decompressed if
+        /// needed. For uncompressed files this is a slice of the current
+        /// page, no bytes are copied.
+        pub fn row_bytes(&self) -> &[u8] {
+            match self.cur_row {
+                Some((off, len)) => &self.cached_page[off .. off + len],
+                None => &self.row_buf,
+            }
+        }
+
+        fn from_source(src : Source<R>, opts : &ReadOptions) -> Result<SAS7bdat<R>, SasError> {
+            let mut sas = SAS7bdat::with_header(src, opts)?;
+            sas.parse_metadata()?;
+            Ok(sas)
+        }
+
+        //Creates the reader and parses the file header from src.
+        fn with_header(src : Source<R>, opts : &ReadOptions) -> Result<SAS7bdat<R>, SasError> {
             let mut sas = SAS7bdat{
                 date_base : Utc.with_ymd_and_hms(1960,1,1,0,0,0).unwrap(),
-                float_buf : [0;8],
+                plan : Vec::new(),
+                batch : None,
+                batch_err : None,
                 row_vals : Vec::new(),
                 col_formats : Vec::default(),
+                col_format_specs : Vec::default(),
                 trim_strings : true,
                 no_align_correction : false,
                 date_created : 0.,
@@ -1066,21 +1739,34 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
                 os_type : String::default(),
                 os_name : String::default(),
                 file_type : String::default(),
-                file_encoding : String::default(),
+                opts : opts.clone(),
+                encoding_id : 0,
                 u64 : false,
                 byte_order : Endian::default(),
                 compression : String::default(),
-                text_decoder : Encodings::MultiByte(UTF8Encoding),
+                text_decoder : Encoding::UTF_8,
                 row_count : usize::default(),
                 col_types : Vec::default(),
                 col_labels : Vec::default(),
                 col_names : Vec::default(),
+                col_list : Vec::default(),
+                sub_hdr_counts : Vec::default(),
+                decode_errors : Vec::default(),
                 buf : Vec::default(),
-                buf_rdr : reader,
-                cached_page : Vec::default(),
+                src,
+                cached_page : Page::default(),
+                row_buf : Vec::new(),
+                cur_row : None,
                 cur_page_type : isize::default(),
                 cur_page_block_count : usize::default(),
                 cur_page_sub_hdr_count : usize::default(),
+                cur_page_has_deleted : false,
+                cur_row_deleted : false,
+                deleted_count : 0,
+                pages_read : 0,
+                eof : false,
+                damage : DamageReport::default(),
+                damaged_rows : 0,
                 cur_row_in_file_idx : usize::default(),
                 cur_row_on_page_idx : usize::default(),
                 cur_page_data_sub_hdr_pointers : Vec::default(),
@@ -1088,20 +1774,81 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
                 col_name_strings : Vec::default(),
                 col_data_off : Vec::default(),
                 col_data_lens : Vec::default(),
-                cols : Vec::default(),
                 props : SasProperties::default(),
                 hdr_sig_map : get_hdr_sig_map(),
             };
             sas.get_properties()?;
-            sas.cached_page = vec![0;sas.props.page_len];
-            sas.parse_metadata()?;
-            sas.row_vals = vec![SasVal::Numeric(0.0);sas.props.col_cnt];
             Ok(sas)
         }
     }
 
-    fn rle_decompress(res_len : usize, input : &[u8]) -> Result<Vec<u8>, SasError>{
-        let mut res : Vec<u8> = Vec::with_capacity(res_len);
+    //Bounds checked reads of compressed rows, which may be corrupt.
+    fn in_byte(input : &[u8], idx : usize) -> Result<u8, SasError>{
+        input.get(idx).copied().ok_or(SasError::UnexpectedEndOfControlByte)
+    }
+
+    fn in_slice(input : &[u8], idx : usize, len : usize) -> Result<&[u8], SasError>{
+        input.get(idx..idx + len).ok_or(SasError::UnexpectedEndOfControlByte)
+    }
+
+    //A row being decompressed into the caller's buffer, which must be filled
+    //exactly.
+    struct RowOut<'a>{
+        buf : &'a mut [u8],
+        len : usize,
+    }
+
+    impl RowOut<'_>{
+        fn fill(&mut self, n : usize, byte : u8) -> Result<(), SasError>{
+            let end = self.len + n;
+            match self.buf.get_mut(self.len..end){
+                Some(val) => val.fill(byte),
+                None => return Err(SasError::BufLen),
+            }
+            self.len = end;
+            Ok(())
+        }
+
+        fn copy(&mut self, bytes : &[u8]) -> Result<(), SasError>{
+            let end = self.len + bytes.len();
+            match self.buf.get_mut(self.len..end){
+                Some(val) => val.copy_from_slice(bytes),
+                None => return Err(SasError::BufLen),
+            }
+            self.len = end;
+            Ok(())
+        }
+
+        //Appends cnt bytes found ofs bytes back in the output. The copy may
+        //overlap the bytes it appends, e.g. a run of a pattern.
+        fn back_ref(&mut self, ofs : u16, cnt : u16) -> Result<(), SasError>{
+            let start = match self.len.checked_sub(usize::from(ofs)){
+                Some(val) if ofs > 0 => val,
+                _ => return Err(SasError::Cmd),
+            };
+            let end = self.len + usize::from(cnt);
+            if end > self.buf.len(){
+                return Err(SasError::BufLen);
+            }
+            for i in start..end - usize::from(ofs){
+                self.buf[i + usize::from(ofs)] = self.buf[i];
+            }
+            self.len = end;
+            Ok(())
+        }
+
+        fn finish(&self) -> Result<(), SasError>{
+            if self.len != self.buf.len(){
+                return Err(SasError::BufLen);
+            }
+            Ok(())
+        }
+    }
+
+    /// Decompresses a SASYZCRL (run length) compressed row into `out`, which
+    /// must have the length of the row.
+    pub fn rle_decompress(input : &[u8], out : &mut [u8]) -> Result<(), SasError>{
+        let mut res = RowOut{buf : out, len : 0};
         let len = input.len();
         let mut cur_idx = 0;
         while cur_idx < len{
@@ -1110,161 +1857,144 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
             cur_idx += 1;
             match control_byte {
                 0x00 => {
-                    let nbytes = usize::from(input[cur_idx]) + 64 + end_of_first_byte * 256;
+                    let nbytes = usize::from(in_byte(input, cur_idx)?) + 64 + end_of_first_byte * 256;
                     cur_idx += 1;
-                    res.extend_from_slice(&input[cur_idx..cur_idx + nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes 
                 }
                 0x10 => {
-                    let nbytes = usize::from(input[cur_idx]) + 64 + end_of_first_byte * 256 + 4096;
+                    let nbytes = usize::from(in_byte(input, cur_idx)?) + 64 + end_of_first_byte * 256 + 4096;
                     cur_idx += 1;
-                    res.extend_from_slice(&input[cur_idx..cur_idx +  nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes;
 
                 }
                 0x20 => {
                     let nbytes = end_of_first_byte + 96;
-                    res.extend_from_slice(&input[cur_idx..cur_idx + nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes;
                 }
                 0x40 => {
-                    let nbytes = end_of_first_byte * 256 + usize::from(input[cur_idx]) + 18;
+                    let nbytes = end_of_first_byte * 256 + usize::from(in_byte(input, cur_idx)?) + 18;
                     cur_idx += 1;
-                    for _ in 0..nbytes{
-                        res.push(input[cur_idx]);
-                    }
+                    res.fill(nbytes, in_byte(input, cur_idx)?)?;
                     cur_idx += 1;
                 }
+                0x50 => {
+                    let nbytes = end_of_first_byte * 256 + usize::from(in_byte(input, cur_idx)?) + 17;
+                    cur_idx += 1;
+                    res.fill(nbytes, 0x40)?;
+                }
                 0x60 => {
-                    let nbytes = end_of_first_byte * 256 + usize::from(input[cur_idx]) + 17;
+                    let nbytes = end_of_first_byte * 256 + usize::from(in_byte(input, cur_idx)?) + 17;
                     cur_idx += 1;
-                    for _ in 0..nbytes{
-                        res.push(0x20);
-                    }
+                    res.fill(nbytes, 0x20)?;
                 }
                 0x70 => {
-                    let nbytes = end_of_first_byte * 256 + usize::from(input[cur_idx]) + 17;
+                    let nbytes = end_of_first_byte * 256 + usize::from(in_byte(input, cur_idx)?) + 17;
                     cur_idx += 1;
-                    for _ in 0..nbytes{
-                        res.push(0x00);
-                    }
+                    res.fill(nbytes, 0x00)?;
                 }
                 0x80 => {
                     let nbytes = end_of_first_byte + 1;
-                    res.extend_from_slice(&input[cur_idx.. cur_idx + nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes;
                 }
                 0x90 => {
                     let nbytes = end_of_first_byte + 17;
-                    res.extend_from_slice(&input[cur_idx.. cur_idx + nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes;
                 }
                 0xA0 => {
                     let nbytes = end_of_first_byte + 33;
-                    res.extend_from_slice(&input[cur_idx.. cur_idx + nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes;
                 }
                 0xB0 => {
                     let nbytes = end_of_first_byte + 49;
-                    res.extend_from_slice(&input[cur_idx..cur_idx + nbytes]);
+                    res.copy(in_slice(input, cur_idx, nbytes)?)?;
                     cur_idx += nbytes;
                 }
                 0xC0 => {
                     let nbytes = end_of_first_byte + 3;
-                    let x = input[cur_idx];
+                    let x = in_byte(input, cur_idx)?;
                     cur_idx += 1;
-                    for _ in 0..nbytes{
-                        res.push(x);
-                    }
+                    res.fill(nbytes, x)?;
                 }
                 0xD0 => {
                     let nbytes = end_of_first_byte + 2;
-                    for _ in 0..nbytes{
-                        res.push(0x40);
-                    }
+                    res.fill(nbytes, 0x40)?;
                 }
                 0xE0 => {
                     let nbytes = end_of_first_byte + 2;
-                    for _ in 0..nbytes{
-                        res.push(0x20);
-                    }
+                    res.fill(nbytes, 0x20)?;
                 }
                 0xF0 => {
                     let nbytes = end_of_first_byte + 2;
-                    for _ in 0..nbytes{
-                        res.push(0x00);
-                    }
+                    res.fill(nbytes, 0x00)?;
                 }
                 _ => { return Err(SasError::ControlByte); }
             }
         }
-
-        if res.len() != res_len{
-            //println!("rle");
-            //println!("cur_len {} is not equalt to requested_len {res_len}", res.len());
-            return Err(SasError::BufLen);
-        }
-
-        Ok(res)
+        res.finish()
     }
 
-    fn rdc_decompress(res_len : usize, inbuf : &[u8]) -> Result<Vec<u8>, SasError>{
+    /// Decompresses a SASYZCR2 (Ross data compression) compressed row into
+    /// `out`, which must have the length of the row.
+    pub fn rdc_decompress(inbuf : &[u8], out : &mut [u8]) -> Result<(), SasError>{
         let mut ctrl_bits : u16 = 0;
         let mut ctrl_mask : u16 = 0;
         let mut cmd : u8;
         let mut ofs : u16;
         let mut cnt : u16;
         let mut inbuf_pos : usize = 0;
-        let mut res : Vec<u8> = Vec::with_capacity(res_len);
+        let mut res = RowOut{buf : out, len : 0};
 
         while inbuf_pos < inbuf.len(){
             ctrl_mask >>= 1;
             if ctrl_mask == 0{
-                ctrl_bits = u16::from(inbuf[inbuf_pos]) << 8 + u16::from(inbuf[inbuf_pos+1]);
+                ctrl_bits = (u16::from(in_byte(inbuf, inbuf_pos)?) << 8) + u16::from(in_byte(inbuf, inbuf_pos + 1)?);
                 inbuf_pos += 2;
                 ctrl_mask = 0x8000;
             }
             if (ctrl_bits & ctrl_mask) == 0 {
-                res.push(inbuf[inbuf_pos]);
+                res.fill(1, in_byte(inbuf, inbuf_pos)?)?;
                 inbuf_pos += 1;
                 continue;
             }
-            cmd = (inbuf[inbuf_pos] >> 4) & 0x0F;
-            cnt = u16::from(inbuf[inbuf_pos] & 0x0F);
+            cmd = (in_byte(inbuf, inbuf_pos)? >> 4) & 0x0F;
+            cnt = u16::from(in_byte(inbuf, inbuf_pos)? & 0x0F);
             inbuf_pos += 1;
 
             match cmd{
                 0 => {
                     cnt += 3;
-                    for _ in 0..usize::from(cnt){
-                        res.push(inbuf[inbuf_pos]);
-                    }
+                    let x = in_byte(inbuf, inbuf_pos)?;
+                    res.fill(usize::from(cnt), x)?;
                     inbuf_pos += 1;
                 }
                 1 => {
-                    cnt += u16::from(inbuf[inbuf_pos]) << 4;
+                    cnt += u16::from(in_byte(inbuf, inbuf_pos)?) << 4;
                     cnt += 19;
                     inbuf_pos += 1;
-                    for _ in 0..usize::from(cnt){
-                        res.push(inbuf[inbuf_pos]);
-                    }
+                    let x = in_byte(inbuf, inbuf_pos)?;
+                    res.fill(usize::from(cnt), x)?;
                     inbuf_pos += 1;
                 }
                 2 => {
                     ofs = cnt + 3;
-                    ofs += u16::from(inbuf[inbuf_pos]) << 4;
+                    ofs += u16::from(in_byte(inbuf, inbuf_pos)?) << 4;
                     inbuf_pos += 1;
-                    cnt = u16::from(inbuf[inbuf_pos]);
+                    cnt = u16::from(in_byte(inbuf, inbuf_pos)?);
                     inbuf_pos += 1;
                     cnt += 16;
-                    res.extend_from_within((res.len() -usize::from(ofs))..(res.len()-usize::from(ofs)+usize::from(cnt)));
+                    res.back_ref(ofs, cnt)?;
                 }
                 3..=16 => {
                     ofs = cnt + 3;
-                    ofs += u16::from(inbuf[inbuf_pos]) << 4;
+                    ofs += u16::from(in_byte(inbuf, inbuf_pos)?) << 4;
                     inbuf_pos += 1;
-                    res.extend_from_within((res.len() - usize::from(ofs))..
-                                           (res.len() - usize::from(ofs) + usize::from(cmd)));
+                    res.back_ref(ofs, u16::from(cmd))?;
                 }
                 _ => {
                     return Err(SasError::Cmd);
@@ -1272,10 +2002,5 @@ impl<R : std::io::Read + std::io::Seek> SAS7bdat<R>{
 
             }
         }
-
-        if res.len() != res_len {
-            return Err(SasError::BufLen);
-        }
-
-        Ok(res)
+        res.finish()
     }
@@ -0,0 +1,236 @@
+use std::io::{self, BufWriter, Read, Write};
+use std::process::ExitCode;
+use sas::*;
+
+const USAGE : &str = "Usage: sas <command> [options] <file>
+
+Commands:
+    info        print the dataset metadata
+    cat         print the rows as csv
+    validate    check the structure of the file
+
+Options:
+    --member <name>     member of a zip archive to read
+    --encoding <name>   read the text with this encoding, e.g. utf-8
+    --salvage           skip damaged pages and rows, and report them
+    --prefetch <pages>  read up to this many pages ahead on a second thread
+    --json              print the validation report as json
+    --formatted         print the values of cat with the column formats
+    --count-deleted     scan the rows in info to count the deleted ones
+
+Gzip, zstd, xz and zip input is detected by its magic bytes.
+Use - as file to read from stdin.";
+
+struct Args{
+    cmd : String,
+    path : String,
+    member : Option<String>,
+    encoding : Option<Encoding>,
+    salvage : bool,
+    prefetch : usize,
+    json : bool,
+    formatted : bool,
+    count_deleted : bool,
+}
+
+fn parse_args() -> Result<Args, String>{
+    let mut cmd = None;
+    let mut path = None;
+    let mut member = None;
+    let mut encoding = None;
+    let mut salvage = false;
+    let mut prefetch = 0;
+    let mut json = false;
+    let mut formatted = false;
+    let mut count_deleted = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next(){
+        match arg.as_str(){
+            "--member" => match args.next(){
+                Some(val) => member = Some(val),
+                None => return Err("--member needs a value".to_string()),
+            }
+            "--encoding" => match args.next(){
+                Some(val) => match Encoding::from_name(&val){
+                    Some(x) if x.is_supported() => encoding = Some(x),
+                    _ => return Err(format!("unknown encoding {val}")),
+                }
+                None => return Err("--encoding needs a value".to_string()),
+            }
+            "--salvage" => salvage = true,
+            "--prefetch" => match args.next().map(|x| x.parse()){
+                Some(Ok(val)) => prefetch = val,
+                _ => return Err("--prefetch needs a number of pages".to_string()),
+            }
+            "--json" => json = true,
+            "--formatted" => formatted = true,
+            "--count-deleted" => count_deleted = true,
+            "-h" | "--help" => return Err(String::new()),
+            x if x.starts_with("--") => return Err(format!("unknown option {x}")),
+            _ if cmd.is_none() => cmd = Some(arg),
+            _ if path.is_none() => path = Some(arg),
+            x => return Err(format!("unexpected argument {x}")),
+        }
+    }
+    match (cmd, path){
+        (Some(cmd), _) if !matches!(cmd.as_str(), "info" | "cat" | "validate") => Err(format!("unknown command {cmd}")),
+        (Some(cmd), Some(path)) => Ok(Args{cmd, path, member, encoding, salvage, prefetch, json, formatted, count_deleted}),
+        _ => Err(String::new()),
+    }
+}
+
+fn open(args : &Args, mut opts : ReadOptions) -> Result<SAS7bdat<Box<dyn Read + Send>>, SasError>{
+    opts = opts.salvage(args.salvage);
+    if let Some(encoding) = args.encoding{
+        opts = opts.encoding(encoding);
+    }
+    match (args.path.as_str(), &args.member){
+        ("-", None) if args.prefetch > 0 => opts.read(Box::new(PrefetchReader::new(decompress(io::stdin())?, args.prefetch))),
+        ("-", None) => opts.read(decompress(io::stdin())?),
+        ("-", Some(_)) => Err(SasError::Codec("zip members cannot be read from stdin".to_string())),
+        (path, member) => opts.read(open_reader(path, member.as_deref(), args.prefetch)?),
+    }
+}
+
+fn print_info<R : Read>(sas : &SAS7bdat<R>, counted : bool) -> io::Result<()>{
+    let mut out = io::stdout().lock();
+    writeln!(out, "name    : {}", sas.name())?;
+    writeln!(out, "rows    : {}", sas.row_count())?;
+    if counted {
+        writeln!(out, "deleted : {}", sas.deleted_count())?;
+    } else {
+        writeln!(out, "deleted : unknown, see --count-deleted")?;
+    }
+    writeln!(out, "encoding: {}", sas.encoding())?;
+    writeln!(out, "columns : {}", sas.col_names().len())?;
+    for (idx, name) in sas.col_names().iter().enumerate(){
+        let format = match &sas.col_format_specs()[idx]{
+            x if x.name.is_empty() && x.width == 0 => String::new(),
+            x => x.to_string(),
+        };
+        writeln!(out, "  {name:<32} {format:<16} {}", sas.col_label()[idx])?;
+    }
+    Ok(())
+}
+
+//The first rows are sampled to check the encoding named in the header. All
+//rows are only scanned to count the deleted ones when asked to.
+fn info<R : Read>(sas : &mut SAS7bdat<R>, count_deleted : bool) -> Result<(), SasError>{
+    let guess = sas.detect_encoding(1000)?;
+    if count_deleted {
+        while sas.read_line()?{}
+    }
+    print_info(sas, count_deleted).map_err(SasError::Io)?;
+    if let Some(header) = Encoding::from_id(sas.encoding_id()){
+        if guess.encoding != header && guess.confidence >= 0.5{
+            eprintln!("warning: the header names the encoding {header}, but the text looks like {} (confidence {:.2})",
+                      guess.encoding, guess.confidence);
+        }
+    }
+    Ok(())
+}
+
+fn csv_field(val : &str) -> String{
+    if val.contains([',', '"', '\n', '\r']){
+        format!("\"{}\"", val.replace('"', "\"\""))
+    } else {
+        val.to_string()
+    }
+}
+
+fn cat<R : Read>(sas : &mut SAS7bdat<R>, formatted : bool) -> Result<(), SasError>{
+    let mut out = BufWriter::new(io::stdout().lock());
+    let header : Vec<String> = sas.col_names.iter().map(|x| csv_field(x)).collect();
+    writeln!(out, "{}", header.join(",")).map_err(SasError::Io)?;
+    let formats = sas.col_format_specs().clone();
+    while sas.read_line()?{
+        if formatted {
+            let row : Vec<String> = sas.row_vals.iter().zip(formats.iter()).map(|(val, fmt)| csv_field(&val.format(fmt))).collect();
+            writeln!(out, "{}", row.join(",")).map_err(SasError::Io)?;
+            continue;
+        }
+        let row : Vec<String> = sas.row_vals.iter().map(|val| match val{
+            SasVal::Numeric(x) if x.is_nan() => String::new(),
+            SasVal::Numeric(x) => x.to_string(),
+            SasVal::Text(x) => csv_field(x),
+            SasVal::Date(x) => x.to_string(),
+            SasVal::DateTime(x) => x.naive_utc().to_string(),
+            SasVal::Time(x) => x.to_string(),
+            SasVal::Bytes(x) => csv_field(&String::from_utf8_lossy(x)),
+        }).collect();
+        writeln!(out, "{}", row.join(",")).map_err(SasError::Io)?;
+    }
+    out.flush().map_err(SasError::Io)
+}
+
+fn print_damage(report : &DamageReport){
+    for x in report.damaged.iter(){
+        let rows = match x.rows_lost{
+            Some(n) => format!("{n} rows"),
+            None => "page".to_string(),
+        };
+        eprintln!("damaged: page {} bytes {}..{} ({rows}): {}", x.page, x.start, x.end, x.error);
+    }
+    if !report.damaged.is_empty() || report.rows_lost > 0{
+        eprintln!("rows lost: {}", report.rows_lost);
+    }
+}
+
+//Returns false if the file is damaged.
+fn validate_file(args : &Args) -> Result<bool, SasError>{
+    let report = match args.path.as_str(){
+        "-" => validate(decompress(io::stdin())?),
+        path => validate(open_reader(path, args.member.as_deref(), 0)?),
+    };
+    let mut out = io::stdout().lock();
+    if args.json{
+        writeln!(out, "{}", report.to_json()).map_err(SasError::Io)?;
+    } else {
+        writeln!(out, "{report}").map_err(SasError::Io)?;
+    }
+    Ok(report.is_valid())
+}
+
+fn run(args : &Args) -> Result<(), SasError>{
+    let mut sas = match args.cmd.as_str(){
+        //info does not print cells, so they are not decoded
+        "info" => open(args, ReadOptions::default().decode_policy(DecodePolicy::Raw))?,
+        _ => open(args, ReadOptions::default())?,
+    };
+    match args.cmd.as_str(){
+        "info" => info(&mut sas, args.count_deleted)?,
+        _ => cat(&mut sas, args.formatted)?,
+    }
+    print_damage(sas.damage_report());
+    Ok(())
+}
+
+fn main() -> ExitCode{
+    let args = match parse_args(){
+        Ok(args) => args,
+        Err(msg) => {
+            if !msg.is_empty(){
+                eprintln!("{msg}");
+            }
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.cmd == "validate"{
+        return match validate_file(&args){
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => ExitCode::FAILURE,
+            Err(er) => {
+                eprintln!("{}: {er:?}", args.path);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    match run(&args){
+        Ok(()) => ExitCode::SUCCESS,
+        Err(er) => {
+            eprintln!("{}: {er:?}", args.path);
+            ExitCode::FAILURE
+        }
+    }
+}
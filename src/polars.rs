@@ -0,0 +1,158 @@
+//! Polars integration, with the `polars` feature.
+//!
+//! ```no_run
+//! use polars::prelude::*;
+//! use sas::ReadOptions;
+//!
+//! let df = sas::polars::read_sas7bdat("data.sas7bdat", &ReadOptions::default()).unwrap();
+//! let head = sas::polars::scan_sas7bdat("data.sas7bdat", ReadOptions::default()).unwrap()
+//!     .select([col("id"), col("visit")])
+//!     .limit(100)
+//!     .collect()
+//!     .unwrap();
+//! ```
+//!
+//! Numerics become `Float64`, character columns `String` (`Binary` with
+//! `DecodePolicy::Raw`), dates `Date`, datetimes `Datetime` in microseconds
+//! and times `Duration`s, as SAS times may exceed a day. Missing values are
+//! nulls.
+use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use ::polars::prelude::*;
+use polars_arrow::bitmap::Bitmap;
+use crate::{BatchColumn, ColumnBatch, DecodePolicy, ReadOptions, SasError, Validity};
+
+/// Reads a whole file into a `DataFrame`.
+pub fn read_sas7bdat<P : AsRef<Path>>(path : P, options : &ReadOptions) -> PolarsResult<DataFrame>{
+    SasScan::new(path, options.clone())?.read(None, None)
+}
+
+/// Scans a file lazily, see `SasScan`.
+pub fn scan_sas7bdat<P : AsRef<Path>>(path : P, options : ReadOptions) -> PolarsResult<LazyFrame>{
+    SasScan::new(path, options)?.finish()
+}
+
+/// Source of a `LazyFrame` reading a sas7bdat file. Only the selected
+/// columns are decoded and a `limit` stops the reader early. Polars columns
+/// carry no metadata, the labels are kept here instead.
+pub struct SasScan{
+    path : PathBuf,
+    options : ReadOptions,
+    schema : SchemaRef,
+    names : Vec<String>,
+    labels : Vec<String>,
+}
+
+impl SasScan{
+    /// Reads the metadata of the file at `path`.
+    pub fn new<P : AsRef<Path>>(path : P, options : ReadOptions) -> PolarsResult<SasScan>{
+        let path = path.as_ref().to_path_buf();
+        let mut sas = options.open(&path).map_err(to_polars)?;
+        let names = sas.col_names().clone();
+        let labels = sas.col_label().clone();
+        //an empty batch has the types of the columns
+        let empty = sas.read_batch(0).map_err(to_polars)?;
+        let schema = to_frame(empty, &names, options.decode_policy)?.schema().clone();
+        Ok(SasScan{path, options, schema, names, labels})
+    }
+
+    pub fn schema(&self) -> &SchemaRef{
+        &self.schema
+    }
+
+    /// Label of each column, in the order of the schema. Empty if a column
+    /// has none.
+    pub fn labels(&self) -> &[String]{
+        &self.labels
+    }
+
+    pub fn finish(self) -> PolarsResult<LazyFrame>{
+        let args = ScanArgsAnonymous{schema : Some(self.schema.clone()), name : "sas7bdat", ..ScanArgsAnonymous::default()};
+        LazyFrame::anonymous_scan(Arc::new(self), args)
+    }
+
+    fn read(&self, cols : Option<&[PlSmallStr]>, n_rows : Option<usize>) -> PolarsResult<DataFrame>{
+        let cols : Vec<usize> = match cols{
+            Some(cols) => cols.iter()
+                .map(|name| self.names.iter().position(|x| x == name.as_str()).ok_or_else(|| polars_err!(ColumnNotFound: "{name}")))
+                .collect::<PolarsResult<_>>()?,
+            None => (0..self.names.len()).collect(),
+        };
+        let mut sas = self.options.open(&self.path).map_err(to_polars)?;
+        let names : Vec<&str> = cols.iter().map(|j| self.names[*j].as_str()).collect();
+        let n = n_rows.unwrap_or(usize::MAX);
+        let mut df = to_frame(sas.read_batch_columns(n, &cols).map_err(to_polars)?, &names, self.options.decode_policy)?;
+        //a shorter batch is the end of the file or the rows before an error,
+        //which the next call returns
+        while df.height() < n{
+            let batch = sas.read_batch_columns(n - df.height(), &cols).map_err(to_polars)?;
+            if batch.is_empty(){
+                break;
+            }
+            df.vstack_mut(&to_frame(batch, &names, self.options.decode_policy)?)?;
+        }
+        df.rechunk_mut();
+        Ok(df)
+    }
+}
+
+impl AnonymousScan for SasScan{
+    fn as_any(&self) -> &dyn Any{
+        self
+    }
+
+    fn scan(&self, args : AnonymousScanArgs) -> PolarsResult<DataFrame>{
+        self.read(args.with_columns.as_deref(), args.n_rows)
+    }
+
+    fn schema(&self, _infer_schema_length : Option<usize>) -> PolarsResult<SchemaRef>{
+        Ok(self.schema.clone())
+    }
+
+    fn allows_projection_pushdown(&self) -> bool{
+        true
+    }
+
+    fn allows_slice_pushdown(&self) -> bool{
+        true
+    }
+}
+
+fn to_polars(er : SasError) -> PolarsError{
+    polars_err!(ComputeError: "{er:?}")
+}
+
+fn to_frame<S : AsRef<str>>(batch : ColumnBatch, names : &[S], policy : DecodePolicy) -> PolarsResult<DataFrame>{
+    let columns = batch.columns.into_iter().zip(names)
+        .map(|(col, name)| to_column(col, name.as_ref().into(), policy))
+        .collect::<PolarsResult<Vec<Column>>>()?;
+    DataFrame::new(columns)
+}
+
+fn to_column(col : BatchColumn, name : PlSmallStr, policy : DecodePolicy) -> PolarsResult<Column>{
+    let series = match col{
+        BatchColumn::Numeric{values, validity} => Float64Chunked::from_vec_validity(name, values, bitmap(&validity)).into_series(),
+        BatchColumn::Date{days, validity} => Int32Chunked::from_vec_validity(name, days, bitmap(&validity)).into_date().into_series(),
+        BatchColumn::DateTime{micros, validity} => {
+            Int64Chunked::from_vec_validity(name, micros, bitmap(&validity)).into_datetime(TimeUnit::Microseconds, None).into_series()
+        }
+        BatchColumn::Time{micros, validity} => {
+            Int64Chunked::from_vec_validity(name, micros, bitmap(&validity)).into_duration(TimeUnit::Microseconds).into_series()
+        }
+        BatchColumn::Text{offsets, bytes} if policy == DecodePolicy::Raw => {
+            BinaryChunked::from_iter_values(name, offsets.windows(2).map(|x| &bytes[x[0]..x[1]])).into_series()
+        }
+        //every value was decoded to utf-8, so the offsets are on character
+        //boundaries
+        BatchColumn::Text{offsets, bytes} => {
+            let text = String::from_utf8(bytes).map_err(|er| polars_err!(ComputeError: "{er}"))?;
+            StringChunked::from_iter_values(name, offsets.windows(2).map(|x| &text[x[0]..x[1]])).into_series()
+        }
+    };
+    Ok(series.into_column())
+}
+
+fn bitmap(validity : &Validity) -> Option<Bitmap>{
+    Some(Bitmap::from_u8_slice(validity.bits(), validity.len()))
+}
@@ -0,0 +1,125 @@
+//! PostgreSQL binary `COPY` output and `CREATE TABLE` statements.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//!
+//! let mut sas = sas::SAS7bdat::open("adsl.sas7bdat").unwrap();
+//! std::fs::write("adsl.sql", sas::postgres::create_table(&sas, "adsl")).unwrap();
+//! let out = BufWriter::new(File::create("adsl.copy").unwrap());
+//! sas::postgres::write_copy(&mut sas, out).unwrap();
+//! ```
+//!
+//! The data is then loaded with `COPY adsl FROM '/path/adsl.copy' WITH
+//! (FORMAT binary)`. Numerics are `double precision`, so no precision is
+//! lost, character columns `varchar(n)` with the SAS length (`bytea` with
+//! `DecodePolicy::Raw`), dates `date`, datetimes `timestamp` and times
+//! `interval`, as SAS times may exceed a day. Missing values are `NULL`, as
+//! are dates and datetimes beyond the range of `chrono` and the columns behind
+//! a column without bytes, which the reader does not decode.
+use std::io::{Read, Write};
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use crate::{quote_ident, SAS7bdat, SasError, SasVal};
+
+const SIGNATURE : &[u8] = b"PGCOPY\n\xff\r\n\0";
+//column types for numbers, dates, datetimes, times, raw text and text,
+//varchar with the SAS length
+const TYPES : [&str; 6] = ["double precision", "date", "timestamp", "interval", "bytea", "varchar"];
+
+/// `CREATE TABLE` statement for the columns of `sas`, followed by a
+/// `COMMENT ON COLUMN` statement for every label. `table` is quoted as one
+/// identifier.
+pub fn create_table<R : Read>(sas : &SAS7bdat<R>, table : &str) -> String{
+    let table = quote_ident(table);
+    let types = column_types(sas);
+    let columns : Vec<String> = sas.col_names.iter().zip(types).enumerate().map(|(j, (name, kind))| match kind{
+        "varchar" => format!("    {} varchar({})", quote_ident(name), sas.plan.get(j).map_or(1, |x| x.len.max(1))),
+        _ => format!("    {} {kind}", quote_ident(name)),
+    }).collect();
+    let mut ddl = format!("CREATE TABLE {table} (\n{}\n);\n", columns.join(",\n"));
+    for (name, label) in sas.col_names.iter().zip(sas.col_labels.iter()){
+        if !label.is_empty() {
+            ddl += &format!("COMMENT ON COLUMN {table}.{} IS '{}';\n", quote_ident(name), label.replace('\'', "''"));
+        }
+    }
+    ddl
+}
+
+/// Writes the remaining rows of `sas` in the binary `COPY` format, in the
+/// order of the columns of `create_table`. Returns the number of rows
+/// written.
+pub fn write_copy<R : Read, W : Write>(sas : &mut SAS7bdat<R>, mut out : W) -> Result<usize, SasError>{
+    let types = column_types(sas);
+    let decoded = sas.plan.len();
+    let fields = i16::try_from(types.len())
+        .map_err(|_| SasError::SasProperty(format!("{} columns, COPY takes at most {}", types.len(), i16::MAX)))?;
+    let mut buf = Vec::new();
+    buf.extend_from_slice(SIGNATURE);
+    //flags and length of the header extension
+    buf.extend_from_slice(&[0; 8]);
+    let mut rows = 0;
+    while sas.read_line()?{
+        buf.extend_from_slice(&fields.to_be_bytes());
+        for (j, (val, kind)) in sas.row_vals.iter().zip(types.iter()).enumerate(){
+            if j < decoded{
+                write_field(&mut buf, val, kind);
+            } else {
+                buf.extend_from_slice(&(-1i32).to_be_bytes());
+            }
+        }
+        out.write_all(&buf).map_err(SasError::Io)?;
+        buf.clear();
+        rows += 1;
+    }
+    buf.extend_from_slice(&(-1i16).to_be_bytes());
+    out.write_all(&buf).map_err(SasError::Io)?;
+    out.flush().map_err(SasError::Io)?;
+    Ok(rows)
+}
+
+//type of every column, varchar for the columns without a decode plan
+fn column_types<R>(sas : &SAS7bdat<R>) -> Vec<&'static str>{
+    let mut types = sas.export_types(&TYPES);
+    types.resize(sas.col_names.len(), "varchar");
+    types
+}
+
+//appends the length and the value of one field of the column type `kind`,
+//times as intervals of microseconds, days and months
+fn write_field(buf : &mut Vec<u8>, val : &SasVal, kind : &str){
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let interval = |buf : &mut Vec<u8>, micros : i64| {
+        buf.extend_from_slice(&16i32.to_be_bytes());
+        buf.extend_from_slice(&micros.to_be_bytes());
+        buf.extend_from_slice(&[0; 8]);
+    };
+    match val{
+        SasVal::Numeric(x) if x.is_nan() => buf.extend_from_slice(&(-1i32).to_be_bytes()),
+        SasVal::Numeric(x) if kind == "interval" => interval(buf, (x * 1e6).round() as i64),
+        //dates and datetimes the reader could not convert
+        SasVal::Numeric(_) if kind == "date" || kind == "timestamp" => buf.extend_from_slice(&(-1i32).to_be_bytes()),
+        SasVal::Numeric(x) => {
+            buf.extend_from_slice(&8i32.to_be_bytes());
+            buf.extend_from_slice(&x.to_be_bytes());
+        }
+        SasVal::Text(x) => bytes(buf, x.as_bytes()),
+        SasVal::Bytes(x) => bytes(buf, x),
+        SasVal::Date(x) => {
+            buf.extend_from_slice(&4i32.to_be_bytes());
+            buf.extend_from_slice(&((*x - epoch).num_days() as i32).to_be_bytes());
+        }
+        SasVal::DateTime(x) => {
+            let micros = (x.naive_utc() - epoch.and_time(NaiveTime::MIN)).num_microseconds().unwrap_or(i64::MIN);
+            buf.extend_from_slice(&8i32.to_be_bytes());
+            buf.extend_from_slice(&micros.to_be_bytes());
+        }
+        SasVal::Time(x) => interval(buf, x.num_seconds_from_midnight() as i64 * 1_000_000 + x.nanosecond() as i64 / 1000),
+    }
+}
+
+//cells are far shorter than i32::MAX
+fn bytes(buf : &mut Vec<u8>, val : &[u8]){
+    buf.extend_from_slice(&(val.len() as i32).to_be_bytes());
+    buf.extend_from_slice(val);
+}
+
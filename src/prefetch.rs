@@ -0,0 +1,125 @@
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+use crate::checked_layout;
+use crate::source::read_full;
+
+//Chunk size while the page length is not known, e.g. if the header is not a
+//sas7bdat header.
+const CHUNK_LEN : usize = 1 << 16;
+
+/// Reads a file on a background thread, up to `depth` pages ahead, so that
+/// waiting for I/O overlaps with decoding the current page. Helps on network
+/// filesystems, where each read waits for a round trip.
+///
+/// ```no_run
+/// # use sas::{PrefetchReader, ReadOptions};
+/// let file = std::fs::File::open("/mnt/nfs/data.sas7bdat").unwrap();
+/// let mut sas = ReadOptions::default().read(PrefetchReader::new(file, 8)).unwrap();
+/// ```
+///
+/// `open_reader` does the same for files and zip members. The thread stops
+/// once the reader is dropped.
+pub struct PrefetchReader{
+    pages : Receiver<io::Result<Vec<u8>>>,
+    //used pages go back to the thread to be filled again
+    spent : Sender<Vec<u8>>,
+    page : Vec<u8>,
+    pos : usize,
+}
+
+impl PrefetchReader{
+    pub fn new<R : Read + Send + 'static>(reader : R, depth : usize) -> PrefetchReader{
+        PrefetchReader::spawn(depth, move |pump| pump.run(reader))
+    }
+
+    //Runs `read` on the background thread, so that the reader it hands to
+    //the pump may borrow from the thread, e.g. a zip member from its archive.
+    pub(crate) fn spawn<F : FnOnce(Pump) + Send + 'static>(depth : usize, read : F) -> PrefetchReader{
+        let (tx, pages) = mpsc::sync_channel(depth.max(1));
+        let (spent, rx_spent) = mpsc::channel();
+        thread::spawn(move || read(Pump{tx, spent : rx_spent}));
+        PrefetchReader{pages, spent, page : Vec::new(), pos : 0}
+    }
+}
+
+//The sending end of a PrefetchReader.
+pub(crate) struct Pump{
+    tx : SyncSender<io::Result<Vec<u8>>>,
+    spent : Receiver<Vec<u8>>,
+}
+
+impl Pump{
+    pub(crate) fn run<R : Read>(self, rdr : R){
+        prefetch(rdr, self.tx, self.spent)
+    }
+
+    //Ends the stream with `er`.
+    #[cfg(feature = "zip")]
+    pub(crate) fn fail(self, er : io::Error){
+        let _ = self.tx.send(Err(er));
+    }
+}
+
+impl Read for PrefetchReader{
+    fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>{
+        if self.pos == self.page.len(){
+            let next = match self.pages.recv(){
+                Ok(val) => val?,
+                //the thread is done
+                Err(_) => return Ok(0),
+            };
+            let _ = self.spent.send(std::mem::replace(&mut self.page, next));
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.page.len() - self.pos);
+        buf[..n].copy_from_slice(&self.page[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+//Sends the header, then one page at a time, until the end of the file, an
+//error or until the reader is dropped.
+fn prefetch<R : Read>(mut rdr : R, tx : SyncSender<io::Result<Vec<u8>>>, spent : Receiver<Vec<u8>>){
+    let mut hdr = vec![0;288];
+    let mut len = match read_full(&mut rdr, &mut hdr){
+        Ok(val) => val,
+        Err(er) => {
+            let _ = tx.send(Err(er));
+            return;
+        }
+    };
+    hdr.truncate(len);
+    //a corrupt header is reported by the reader
+    let page_len = match checked_layout(&hdr){
+        Ok((hdr_len, val)) => {
+            len = hdr_len;
+            val
+        }
+        Err(_) => CHUNK_LEN,
+    };
+    let mut page = hdr;
+    loop{
+        let old_len = page.len();
+        page.resize(len, 0);
+        let n = match read_full(&mut rdr, &mut page[old_len..]){
+            Ok(val) => val,
+            Err(er) => {
+                let _ = tx.send(Err(er));
+                return;
+            }
+        };
+        page.truncate(old_len + n);
+        if page.is_empty(){
+            return;
+        }
+        let last = page.len() < len;
+        if tx.send(Ok(page)).is_err() || last{
+            return;
+        }
+        page = spent.try_recv().unwrap_or_default();
+        page.clear();
+        len = page_len;
+    }
+}
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use crate::{checked_layout, ReadOptions, SAS7bdat, SasError};
+use crate::source::read_full;
+
+/// Random access to the bytes of a file, such as a local file, a buffer or
+/// an object in a bucket.
+pub trait RangeSource{
+    /// Reads `buf.len()` bytes starting at `offset`. Returns the number of
+    /// read bytes, which is only smaller than `buf.len()` at the end of the
+    /// file.
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>;
+}
+
+impl RangeSource for File{
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>{
+        self.seek(SeekFrom::Start(offset))?;
+        read_full(self, buf)
+    }
+}
+
+impl RangeSource for &[u8]{
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>{
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(self.len());
+        let n = buf.len().min(self.len() - start);
+        buf[..n].copy_from_slice(&self[start..start + n]);
+        Ok(n)
+    }
+}
+
+impl RangeSource for Vec<u8>{
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>{
+        self.as_slice().read_at(offset, buf)
+    }
+}
+
+/// Sends http range requests. Implement it to plug in another http client
+/// or to add authentication.
+pub trait HttpClient{
+    /// Fetches the bytes `start..end` of `url`. Fewer bytes are returned
+    /// when the object ends before `end`.
+    fn get_range(&mut self, url : &str, start : u64, end : u64) -> io::Result<Vec<u8>>;
+}
+
+/// An object read over http with range requests.
+pub struct HttpRange<C>{
+    client : C,
+    url : String,
+}
+
+impl<C : HttpClient> HttpRange<C>{
+    pub fn new(url : &str, client : C) -> HttpRange<C>{
+        HttpRange{client, url : url.to_string()}
+    }
+}
+
+impl<C : HttpClient> RangeSource for HttpRange<C>{
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>{
+        let data = self.client.get_range(&self.url, offset, offset + buf.len() as u64)?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+/// `HttpClient` on top of a `ureq` agent.
+#[cfg(feature = "http")]
+pub struct UreqClient(pub ureq::Agent);
+
+#[cfg(feature = "http")]
+impl HttpClient for UreqClient{
+    fn get_range(&mut self, url : &str, start : u64, end : u64) -> io::Result<Vec<u8>>{
+        if end <= start{
+            return Ok(Vec::new());
+        }
+        let resp = match self.0.get(url).set("Range", &format!("bytes={}-{}", start, end - 1)).call(){
+            Ok(val) => val,
+            //range starts after the end of the object
+            Err(ureq::Error::Status(416, _)) => return Ok(Vec::new()),
+            Err(er) => return Err(io::Error::other(er)),
+        };
+        let status = resp.status();
+        let mut data = Vec::new();
+        resp.into_reader().take(end - start).read_to_end(&mut data)?;
+        if status == 200 && start > 0{
+            //the server ignored the range and sent the whole object
+            return Err(io::Error::other(format!("{url} does not support range requests")));
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "http")]
+impl HttpRange<UreqClient>{
+    /// Reads `url` with a default `ureq` agent.
+    pub fn from_url(url : &str) -> HttpRange<UreqClient>{
+        HttpRange::new(url, UreqClient(ureq::Agent::new()))
+    }
+}
+
+const DEFAULT_READ_SIZE : usize = 1 << 20;
+const MAX_READ_SIZE : usize = 1 << 30;
+
+/// Reads a `RangeSource` front to back. Requests are aligned to the pages
+/// of the file: the first one fetches the start of the header, the next ones
+/// groups of whole pages.
+pub struct RangeReader<S>{
+    src : S,
+    pos : u64,
+    buf : Vec<u8>,
+    buf_start : u64,
+    //header length and bytes per request, known after the first request
+    layout : Option<(u64, u64)>,
+    read_size : usize,
+    requests : usize,
+}
+
+impl<S : RangeSource> RangeReader<S>{
+    pub fn new(src : S) -> RangeReader<S>{
+        RangeReader{
+            src,
+            pos : 0,
+            buf : Vec::new(),
+            buf_start : 0,
+            layout : None,
+            read_size : DEFAULT_READ_SIZE,
+            requests : 0,
+        }
+    }
+
+    /// Approximate number of bytes per request, rounded to whole pages.
+    /// Defaults to 1 MiB, at most 1 GiB.
+    pub fn with_read_size(mut self, read_size : usize) -> RangeReader<S>{
+        self.read_size = read_size.clamp(1, MAX_READ_SIZE);
+        self
+    }
+
+    /// Number of requests sent to the source so far.
+    pub fn requests(&self) -> usize{
+        self.requests
+    }
+
+    pub fn into_inner(self) -> S{
+        self.src
+    }
+
+    //Range of the request containing pos.
+    fn request_range(&self, pos : u64) -> (u64, u64){
+        match self.layout{
+            None => (pos, pos + 288),
+            Some((hdr_len, group)) if pos < hdr_len => (pos, hdr_len + group),
+            Some((hdr_len, group)) => {
+                let start = hdr_len + (pos - hdr_len) / group * group;
+                (start, start + group)
+            }
+        }
+    }
+
+    fn fetch(&mut self) -> io::Result<()>{
+        let (start, end) = self.request_range(self.pos);
+        self.buf.resize((end - start) as usize, 0);
+        let n = self.src.read_at(start, &mut self.buf)?;
+        self.buf.truncate(n);
+        self.buf_start = start;
+        self.requests += 1;
+        if self.layout.is_none(){
+            let read_size = self.read_size as u64;
+            self.layout = Some(match checked_layout(&self.buf){
+                Ok((hdr_len, page_len)) => (hdr_len as u64, read_size.div_ceil(page_len as u64) * page_len as u64),
+                //not a sas7bdat file or a corrupt header, the reader reports
+                //the error
+                Err(_) => (0, read_size),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<S : RangeSource> Read for RangeReader<S>{
+    fn read(&mut self, out : &mut [u8]) -> io::Result<usize>{
+        if out.is_empty(){
+            return Ok(0);
+        }
+        let buf_end = self.buf_start + self.buf.len() as u64;
+        if self.pos < self.buf_start || self.pos >= buf_end{
+            self.fetch()?;
+        }
+        let off = (self.pos - self.buf_start) as usize;
+        if off >= self.buf.len(){
+            return Ok(0);
+        }
+        let n = out.len().min(self.buf.len() - off);
+        out[..n].copy_from_slice(&self.buf[off..off + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<S : RangeSource> SAS7bdat<RangeReader<S>>{
+    /// Reads a file through a `RangeSource`. Only the header and the pages
+    /// that are read are fetched, see `RangeReader`.
+    pub fn from_range(src : S) -> Result<Self, SasError>{
+        ReadOptions::default().read_range(src)
+    }
+}
+
+impl ReadOptions{
+    /// Reads a file through a `RangeSource`, see `SAS7bdat::from_range`.
+    pub fn read_range<S : RangeSource>(&self, src : S) -> Result<SAS7bdat<RangeReader<S>>, SasError>{
+        self.read(RangeReader::new(src))
+    }
+}
@@ -0,0 +1,57 @@
+//! `serde::Serialize` for the values and metadata, with the `serde` feature.
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use crate::{Check, Codec, Encoding, SasVal, ValidationReport};
+
+/// Missing values are `None`, dates, datetimes and times ISO-8601 strings,
+/// datetimes without a time zone as SAS has none.
+impl Serialize for SasVal{
+    fn serialize<S : Serializer>(&self, s : S) -> Result<S::Ok, S::Error>{
+        match self{
+            SasVal::Numeric(x) if x.is_nan() => s.serialize_none(),
+            SasVal::Numeric(x) => s.serialize_f64(*x),
+            SasVal::Text(x) => s.serialize_str(x),
+            SasVal::Date(x) => s.collect_str(&x.format("%Y-%m-%d")),
+            SasVal::DateTime(x) => s.collect_str(&x.naive_utc().format("%Y-%m-%dT%H:%M:%S%.f")),
+            SasVal::Time(x) => s.collect_str(&x.format("%H:%M:%S%.f")),
+            SasVal::Bytes(x) => s.serialize_bytes(x),
+        }
+    }
+}
+
+/// Serialized as its SAS name.
+impl Serialize for Encoding{
+    fn serialize<S : Serializer>(&self, s : S) -> Result<S::Ok, S::Error>{
+        s.serialize_str(self.name())
+    }
+}
+
+/// Serialized as its name, which is also the name of its feature, e.g.
+/// `gzip`.
+impl Serialize for Codec{
+    fn serialize<S : Serializer>(&self, s : S) -> Result<S::Ok, S::Error>{
+        s.serialize_str(self.name())
+    }
+}
+
+/// Serialized as its name, e.g. `page_count`.
+impl Serialize for Check{
+    fn serialize<S : Serializer>(&self, s : S) -> Result<S::Ok, S::Error>{
+        s.serialize_str(self.name())
+    }
+}
+
+/// The fields, with `valid` first.
+impl Serialize for ValidationReport{
+    fn serialize<S : Serializer>(&self, s : S) -> Result<S::Ok, S::Error>{
+        let mut st = s.serialize_struct("ValidationReport", 7)?;
+        st.serialize_field("valid", &self.is_valid())?;
+        st.serialize_field("file_len", &self.file_len)?;
+        st.serialize_field("page_count", &self.page_count)?;
+        st.serialize_field("pages", &self.pages)?;
+        st.serialize_field("row_count", &self.row_count)?;
+        st.serialize_field("rows", &self.rows)?;
+        st.serialize_field("issues", &self.issues)?;
+        st.end()
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::ops::Deref;
+#[cfg(feature = "mmap")]
+use std::sync::Arc;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use crate::SasError;
+
+/// The page currently being decoded. Pages read from a stream are owned,
+/// pages of a memory mapped file are windows into the mapping.
+pub(crate) enum Page{
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<Mmap>, usize, usize),
+}
+
+impl Default for Page{
+    fn default() -> Page{
+        Page::Owned(Vec::new())
+    }
+}
+
+impl Deref for Page{
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8]{
+        match self{
+            Page::Owned(x) => x,
+            #[cfg(feature = "mmap")]
+            Page::Mapped(map, start, end) => &map[*start..*end],
+        }
+    }
+}
+
+impl Page{
+    /// Turns the page into an owned buffer. The allocation of an owned page
+    /// is kept, so that it can be reused for the next page.
+    pub(crate) fn owned_mut(&mut self) -> &mut Vec<u8>{
+        match self{
+            Page::Owned(x) => x,
+            #[cfg(feature = "mmap")]
+            Page::Mapped(map, start, end) => {
+                *self = Page::Owned(map[*start..*end].to_vec());
+                self.owned_mut()
+            }
+        }
+    }
+}
+
+/// Where the bytes of the file come from. Both the header and the pages are
+/// read strictly forward.
+pub(crate) enum Source<R>{
+    Stream(BufReader<R>),
+    #[cfg(feature = "mmap")]
+    Mapped{
+        map : Arc<Mmap>,
+        pos : usize,
+        _file : std::fs::File,
+    },
+}
+
+//Fills buf as far as the reader allows, returns the number of read bytes.
+pub(crate) fn read_full<R : Read>(rdr : &mut R, buf : &mut [u8]) -> io::Result<usize>{
+    let mut done = 0;
+    while done < buf.len(){
+        match rdr.read(&mut buf[done..]){
+            Ok(0) => break,
+            Ok(n) => done += n,
+            Err(er) if er.kind() == ErrorKind::Interrupted => (),
+            Err(er) => return Err(er),
+        }
+    }
+    Ok(done)
+}
+
+impl<R : Read> Source<R>{
+    /// Replaces `page` with the next `len` bytes of the file. Returns the
+    /// number of read bytes, which is smaller than `len` at the end of the
+    /// file.
+    pub(crate) fn read_page(&mut self, page : &mut Page, len : usize) -> Result<usize, SasError>{
+        match self{
+            Source::Stream(rdr) => {
+                let buf = page.owned_mut();
+                buf.resize(len, 0);
+                read_full(rdr, buf).map_err(SasError::Io)
+            }
+            #[cfg(feature = "mmap")]
+            Source::Mapped{map, pos, ..} => {
+                if *pos + len > map.len(){
+                    let left = map.len() - *pos;
+                    *pos = map.len();
+                    *page = Page::default();
+                    return Ok(left);
+                }
+                *page = Page::Mapped(Arc::clone(map), *pos, *pos + len);
+                *pos += len;
+                Ok(len)
+            }
+        }
+    }
+
+    /// Appends the next `len` bytes of the file to `page`. Returns false if
+    /// fewer than `len` bytes were left.
+    pub(crate) fn extend_page(&mut self, page : &mut Page, len : usize) -> Result<bool, SasError>{
+        match self{
+            Source::Stream(rdr) => {
+                let buf = page.owned_mut();
+                let old_len = buf.len();
+                buf.resize(old_len + len, 0);
+                Ok(read_full(rdr, &mut buf[old_len..]).map_err(SasError::Io)? == len)
+            }
+            #[cfg(feature = "mmap")]
+            Source::Mapped{map, pos, ..} => {
+                if *pos + len > map.len(){
+                    return Ok(false);
+                }
+                match page{
+                    Page::Mapped(_, _, end) if *end == *pos => *end += len,
+                    _ => page.owned_mut().extend_from_slice(&map[*pos..*pos + len]),
+                }
+                *pos += len;
+                Ok(true)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl crate::SAS7bdat<std::fs::File>{
+    /// Reads a local file through a memory map. Pages are windows into the
+    /// mapping and rows of uncompressed files are decoded without copying,
+    /// see `row_bytes`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by this or any other
+    /// process while the reader is alive, see `memmap2::Mmap::map`.
+    pub unsafe fn from_mmap(file : std::fs::File) -> Result<Self, SasError>{
+        crate::ReadOptions::default().map(file)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl crate::ReadOptions{
+    /// Reads a local file through a memory map, see `SAS7bdat::from_mmap`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the reader is alive.
+    pub unsafe fn map(&self, file : std::fs::File) -> Result<crate::SAS7bdat<std::fs::File>, SasError>{
+        let map = match Mmap::map(&file){
+            Ok(val) => val,
+            Err(er) => return Err(SasError::Io(er)),
+        };
+        crate::SAS7bdat::from_source(Source::Mapped{map : Arc::new(map), pos : 0, _file : file}, self)
+    }
+}
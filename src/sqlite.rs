@@ -0,0 +1,153 @@
+//! SQLite export, with the `sqlite` feature.
+//!
+//! ```no_run
+//! use rusqlite::Connection;
+//! use sas::sqlite::SqliteOptions;
+//!
+//! let mut conn = Connection::open("studies.db").unwrap();
+//! let options = SqliteOptions::default();
+//! for path in ["adsl.sas7bdat", "adae.sas7bdat"] {
+//!     let mut sas = sas::SAS7bdat::open(path).unwrap();
+//!     let table = sas.name().to_lowercase();
+//!     options.write(&mut conn, &table, &mut sas).unwrap();
+//! }
+//! ```
+//!
+//! Numerics are `REAL` and character columns `TEXT` (`BLOB` with
+//! `DecodePolicy::Raw`). Dates, datetimes and times are ISO-8601 `TEXT`, so
+//! SQLite's date functions work on them, in columns declared `DATE`,
+//! `DATETIME` and `TIME`. Times of a day or more keep counting the hours,
+//! e.g. `25:00:00`. Missing values are `NULL`. The labels and formats are
+//! kept in the `_sas_columns` table.
+use std::io::Read;
+use rusqlite::Connection;
+use rusqlite::types::Value;
+use crate::{quote_ident, SAS7bdat, SasError, SasVal};
+
+//column types for numbers, dates, datetimes, times, raw text and text
+const TYPES : [&str; 6] = ["REAL", "DATE", "DATETIME", "TIME", "BLOB", "TEXT"];
+
+/// Options of the SQLite export.
+#[derive(Clone, Debug)]
+pub struct SqliteOptions{
+    batch_size : usize,
+    replace : bool,
+}
+
+impl Default for SqliteOptions{
+    fn default() -> SqliteOptions{
+        SqliteOptions{batch_size : 10_000, replace : false}
+    }
+}
+
+impl SqliteOptions{
+    /// Rows inserted per transaction, 10000 by default.
+    pub fn batch_size(mut self, batch_size : usize) -> SqliteOptions{
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Drops an existing table of the same name instead of appending to it.
+    pub fn replace(mut self, replace : bool) -> SqliteOptions{
+        self.replace = replace;
+        self
+    }
+
+    /// Writes the remaining rows of `sas` to `table`, which is created if
+    /// it does not exist. Rows are appended to an existing table, so several
+    /// files with the same columns can go into one table. Fails if the
+    /// table has other columns or types. Returns the number of rows
+    /// written.
+    pub fn write<R : Read>(&self, conn : &mut Connection, table : &str, sas : &mut SAS7bdat<R>) -> Result<usize, SasError>{
+        let types = sas.export_types(&TYPES);
+        let tx = conn.transaction().map_err(to_sas)?;
+        if self.replace {
+            tx.execute(&format!("DROP TABLE IF EXISTS {}", quote_ident(table)), []).map_err(to_sas)?;
+        }
+        let existing : Vec<(String, String)> = tx.prepare("SELECT name, type FROM pragma_table_info(?1)").map_err(to_sas)?
+            .query_map([table], |x| Ok((x.get(0)?, x.get(1)?))).map_err(to_sas)?
+            .collect::<Result<_, _>>().map_err(to_sas)?;
+        let columns : Vec<(String, String)> = sas.col_names.iter().cloned().zip(types.iter().map(|x| x.to_string())).collect();
+        if !existing.is_empty() && existing != columns {
+            let show = |cols : &[(String, String)]| cols.iter().map(|(name, kind)| format!("{name} {kind}")).collect::<Vec<_>>().join(", ");
+            return Err(SasError::SasProperty(format!("table {table} has the columns ({}), the file has ({})", show(&existing), show(&columns))));
+        }
+        let columns : Vec<String> = sas.col_names.iter().zip(types.iter())
+            .map(|(name, kind)| format!("{} {kind}", quote_ident(name)))
+            .collect();
+        tx.execute(&format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_ident(table), columns.join(", ")), []).map_err(to_sas)?;
+        tx.execute("CREATE TABLE IF NOT EXISTS _sas_columns (
+            table_name TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            type TEXT NOT NULL,
+            label TEXT,
+            format TEXT,
+            PRIMARY KEY (table_name, position))", []).map_err(to_sas)?;
+        tx.execute("DELETE FROM _sas_columns WHERE table_name = ?1", [table]).map_err(to_sas)?;
+        for (j, kind) in types.iter().enumerate(){
+            let label = Some(&sas.col_labels[j]).filter(|x| !x.is_empty());
+            let format = Some(&sas.col_formats[j]).filter(|x| !x.is_empty());
+            tx.execute("INSERT INTO _sas_columns VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                       rusqlite::params![table, j, sas.col_names[j], kind, label, format]).map_err(to_sas)?;
+        }
+        tx.commit().map_err(to_sas)?;
+
+        let names : Vec<String> = sas.col_names.iter().map(|x| quote_ident(x)).collect();
+        let params : Vec<String> = (1..=names.len()).map(|j| format!("?{j}")).collect();
+        let insert = format!("INSERT INTO {} ({}) VALUES ({})", quote_ident(table), names.join(", "), params.join(", "));
+        let mut rows = 0;
+        let mut done = false;
+        while !done {
+            let tx = conn.transaction().map_err(to_sas)?;
+            {
+                let mut stmt = tx.prepare_cached(&insert).map_err(to_sas)?;
+                let mut n = 0;
+                while n < self.batch_size {
+                    if !sas.read_line()? {
+                        done = true;
+                        break;
+                    }
+                    let vals = sas.row_vals.iter().zip(types.iter()).map(|(val, kind)| to_value(val, *kind == "TIME"));
+                    stmt.execute(rusqlite::params_from_iter(vals)).map_err(to_sas)?;
+                    n += 1;
+                }
+                rows += n;
+            }
+            tx.commit().map_err(to_sas)?;
+        }
+        Ok(rows)
+    }
+}
+
+fn to_sas(er : rusqlite::Error) -> SasError{
+    SasError::Io(std::io::Error::other(er))
+}
+
+//`time` for the values of a time column, which are numeric from a day on
+fn to_value(val : &SasVal, time : bool) -> Value{
+    match val{
+        SasVal::Numeric(x) if x.is_nan() => Value::Null,
+        SasVal::Numeric(x) if time => Value::Text(duration(*x)),
+        SasVal::Numeric(x) => Value::Real(*x),
+        SasVal::Text(x) => Value::Text(x.clone()),
+        SasVal::Date(x) => Value::Text(x.format("%Y-%m-%d").to_string()),
+        SasVal::DateTime(x) => Value::Text(x.naive_utc().format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+        SasVal::Time(x) => Value::Text(x.format("%H:%M:%S%.f").to_string()),
+        SasVal::Bytes(x) => Value::Blob(x.clone()),
+    }
+}
+
+//[-]HH:MM:SS with the hours past 24, and the fraction as chrono's %.f
+fn duration(secs : f64) -> String{
+    let sign = if secs < 0.0 { "-" } else { "" };
+    let micros = (secs.abs() * 1e6).round() as u64;
+    let (secs, frac) = (micros / 1_000_000, micros % 1_000_000);
+    let mut out = format!("{sign}{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    if frac % 1000 > 0 {
+        out += &format!(".{frac:06}");
+    } else if frac > 0 {
+        out += &format!(".{:03}", frac / 1000);
+    }
+    out
+}
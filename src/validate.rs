@@ -0,0 +1,330 @@
+use std::cell::Cell;
+use std::fmt;
+use std::io::{self, BufReader, Chain, Cursor, Read};
+use std::rc::Rc;
+use crate::source::{Page, Source};
+use crate::*;
+
+/// The structural checks run by `validate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check{
+    Magic,
+    Header,
+    PageCount,
+    Signature,
+    Subheader,
+    Columns,
+    ColumnLayout,
+    Rows,
+    RowCount,
+    Read,
+}
+
+impl Check{
+    pub fn name(&self) -> &'static str{
+        match self{
+            Check::Magic => "magic",
+            Check::Header => "header",
+            Check::PageCount => "page_count",
+            Check::Signature => "signature",
+            Check::Subheader => "subheader",
+            Check::Columns => "columns",
+            Check::ColumnLayout => "column_layout",
+            Check::Rows => "rows",
+            Check::RowCount => "row_count",
+            Check::Read => "read",
+        }
+    }
+}
+
+impl fmt::Display for Check{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
+        f.write_str(self.name())
+    }
+}
+
+/// A failed check, with the index of the page it was found on.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Issue{
+    pub check : Check,
+    pub page : Option<usize>,
+    pub message : String,
+}
+
+/// Result of `validate`.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport{
+    pub issues : Vec<Issue>,
+    /// Length of the file in bytes.
+    pub file_len : u64,
+    /// Page count and row count from the header.
+    pub page_count : usize,
+    pub row_count : usize,
+    /// Pages and rows found in the file.
+    pub pages : usize,
+    pub rows : usize,
+}
+
+impl ValidationReport{
+    pub fn is_valid(&self) -> bool{
+        self.issues.is_empty()
+    }
+
+    fn add(&mut self, check : Check, page : Option<usize>, message : String){
+        self.issues.push(Issue{check, page, message});
+    }
+
+    /// The report as one JSON object, with a `valid` field.
+    pub fn to_json(&self) -> String{
+        let issues : Vec<String> = self.issues.iter().map(|x| {
+            let page = match x.page{
+                Some(val) => val.to_string(),
+                None => "null".to_string(),
+            };
+            format!("{{\"check\":\"{}\",\"page\":{page},\"message\":{}}}", x.check, json_str(&x.message))
+        }).collect();
+        format!("{{\"valid\":{},\"file_len\":{},\"page_count\":{},\"pages\":{},\"row_count\":{},\"rows\":{},\"issues\":[{}]}}",
+                self.is_valid(), self.file_len, self.page_count, self.pages, self.row_count, self.rows, issues.join(","))
+    }
+}
+
+impl fmt::Display for ValidationReport{
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
+        writeln!(f, "bytes   : {}", self.file_len)?;
+        writeln!(f, "pages   : {} of {}", self.pages, self.page_count)?;
+        writeln!(f, "rows    : {} of {}", self.rows, self.row_count)?;
+        for x in self.issues.iter(){
+            match x.page{
+                Some(page) => writeln!(f, "  [{}] page {page}: {}", x.check, x.message)?,
+                None => writeln!(f, "  [{}] {}", x.check, x.message)?,
+            }
+        }
+        match self.issues.len(){
+            0 => write!(f, "valid"),
+            n => write!(f, "invalid, {n} issues"),
+        }
+    }
+}
+
+fn json_str(val : &str) -> String{
+    let mut out = String::from("\"");
+    for c in val.chars(){
+        match c{
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+//Counts the bytes read from the file, to compare its length with the page
+//count in the header.
+struct Counted<R>{
+    inner : R,
+    n : Rc<Cell<u64>>,
+}
+
+impl<R : Read> Read for Counted<R>{
+    fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>{
+        let n = self.inner.read(buf)?;
+        self.n.set(self.n.get() + n as u64);
+        Ok(n)
+    }
+}
+
+type Checked<R> = SAS7bdat<Chain<Cursor<Vec<u8>>, Counted<R>>>;
+
+/// Checks the structure of a file: magic bytes, header and page sizes, page
+/// count against the file length, subheader signatures, column counts and
+/// layout, and that every row, compressed or not, can be read and that they
+/// add up to the row count of the header. The file is read once, front to
+/// back.
+pub fn validate<R : Read>(reader : R) -> ValidationReport{
+    let mut report = ValidationReport::default();
+    let file_len = Rc::new(Cell::new(0));
+    let mut rdr = Counted{inner : reader, n : Rc::clone(&file_len)};
+    let mut hdr = Vec::with_capacity(288);
+    if let Err(er) = (&mut rdr).take(288).read_to_end(&mut hdr){
+        report.add(Check::Read, None, er.to_string());
+        return report;
+    }
+    if hdr.len() < 288 || hdr[0..MAGIC.len()] != *MAGIC{
+        report.add(Check::Magic, None, "not a sas7bdat file".to_string());
+        return report;
+    }
+    let (hdr_len, page_len) = header_layout(&hdr).unwrap_or_default();
+    if hdr_len < 288{
+        report.add(Check::Header, None, format!("invalid header length {hdr_len}"));
+        return report;
+    }
+    if !(MIN_PAGE_LEN..=MAX_PAGE_LEN).contains(&page_len){
+        report.add(Check::Header, None, format!("invalid page length {page_len}"));
+        return report;
+    }
+
+    let src = Source::Stream(BufReader::new(Cursor::new(hdr).chain(rdr)));
+    //cells are not decoded, text encodings are not part of the structure
+    let opts = ReadOptions::default().encoding(Encoding::WLATIN1).decode_policy(DecodePolicy::Raw)
+        .include_deleted(true).salvage(true);
+    let mut sas = match SAS7bdat::with_header(src, &opts){
+        Ok(val) => val,
+        Err(er) => {
+            report.add(Check::Header, None, format!("{er:?}"));
+            return report;
+        }
+    };
+    if Encoding::from_id(sas.encoding_id).is_none(){
+        report.add(Check::Header, None, format!("unknown encoding id {}", sas.encoding_id));
+    }
+    report.page_count = sas.props.page_count;
+    let readable = check_metadata(&mut sas, &mut report);
+    report.row_count = sas.row_count;
+    check_columns(&sas, &mut report);
+    if readable{
+        check_rows(&mut sas, &mut report);
+    }
+    report.pages = sas.pages_read;
+
+    let mut rest = Page::default();
+    loop{
+        match sas.src.read_page(&mut rest, 1 << 16){
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(er) => {
+                report.add(Check::Read, None, format!("{er:?}"));
+                break;
+            }
+        }
+    }
+    report.file_len = file_len.get();
+    let expected = (sas.props.hdr_len + sas.props.page_count * sas.props.page_len) as u64;
+    if report.file_len != expected{
+        report.add(Check::PageCount, None, format!("{} pages of {} bytes need {expected} bytes, the file has {}",
+                                                   sas.props.page_count, sas.props.page_len, report.file_len));
+    }
+    report
+}
+
+//Reads the metadata pages with the steps of SAS7bdat::parse_metadata, but
+//goes on after a bad subheader. Returns false if the rows cannot be read.
+fn check_metadata<R : Read>(sas : &mut Checked<R>, report : &mut ValidationReport) -> bool{
+    loop{
+        let n = match sas.src.read_page(&mut sas.cached_page, sas.props.page_len){
+            Ok(val) => val,
+            Err(er) => {
+                report.add(Check::Read, Some(sas.pages_read), format!("{er:?}"));
+                return false;
+            }
+        };
+        let page = sas.pages_read;
+        let res = sas.meta_page_read_with(n, |sas, i| {
+            check_sub_hdr(sas, report, page, i);
+            Ok(())
+        });
+        match res{
+            Ok(true) => break,
+            Ok(false) => (),
+            Err(SasError::Truncated(_)) => {
+                report.add(Check::Rows, Some(page), "the file ends in the metadata pages".to_string());
+                return false;
+            }
+            //a bad page header, the next page may be fine
+            Err(er) => report.add(Check::Subheader, Some(page), format!("{er:?}")),
+        }
+    }
+    if let Err(er) = sas.end_metadata(){
+        report.add(Check::Columns, None, format!("{er:?}"));
+        return false;
+    }
+    true
+}
+
+fn check_sub_hdr<R : Read>(sas : &mut Checked<R>, report : &mut ValidationReport, page : usize, i : usize){
+    let off = SUBHEADER_POINTERS_OFFSET + sas.props.page_bit_off;
+    let ptr = match sas.process_sub_hdr_ptrs(off, i){
+        Ok(val) => val,
+        Err(er) => {
+            report.add(Check::Subheader, Some(page), format!("pointer {i}: {er:?}"));
+            return;
+        }
+    };
+    if ptr.len == 0 || ptr.compression == TRUNCATED_SUBHEADER_ID{
+        return;
+    }
+    if ptr.off + ptr.len > sas.props.page_len{
+        report.add(Check::Subheader, Some(page), format!("pointer {i}: subheader at {} of {} bytes exceeds the page",
+                                                         ptr.off, ptr.len));
+        return;
+    }
+    let sig = match sas.read_sub_hdr_sig(ptr.off){
+        Ok(val) => val,
+        Err(er) => {
+            report.add(Check::Subheader, Some(page), format!("pointer {i}: {er:?}"));
+            return;
+        }
+    };
+    let hex : String = sig.iter().map(|x| format!("{x:02x}")).collect();
+    let idx = match sas.get_sub_hdr_idx(sig, ptr.compression, ptr.ptype){
+        Ok(val) => val,
+        Err(_) => {
+            report.add(Check::Signature, Some(page), format!("pointer {i}: unknown subheader signature {hex}"));
+            return;
+        }
+    };
+    if let Err(er) = sas.process_sub_hdr(idx, ptr){
+        let check = if idx == COL_SIZE_IDX { Check::Columns } else { Check::Subheader };
+        report.add(check, Some(page), format!("subheader {hex}: {er:?}"));
+    }
+}
+
+fn check_columns<R : Read>(sas : &Checked<R>, report : &mut ValidationReport){
+    let cnt = sas.props.col_cnt;
+    if sas.props.col_count_p1 + sas.props.col_count_p2 != cnt{
+        report.add(Check::Columns, None, format!("column count mismatch : {} + {} != {cnt}",
+                                                 sas.props.col_count_p1, sas.props.col_count_p2));
+    }
+    for (j, (off, len)) in sas.col_data_off.iter().zip(sas.col_data_lens.iter()).enumerate(){
+        if off + len > sas.props.row_len{
+            let name = sas.col_names.get(j).map_or("", |x| x.as_str());
+            report.add(Check::ColumnLayout, None, format!("column {j} {name} at {off} of {len} bytes exceeds the row length {}",
+                                                          sas.props.row_len));
+        }
+    }
+    for x in sas.subheader_counts().iter(){
+        if x.first_page > sas.props.page_count || x.last_page > sas.props.page_count{
+            report.add(Check::Subheader, None, format!("subheader counts place a subheader on page {} of {}",
+                                                       x.last_page.max(x.first_page), sas.props.page_count));
+        }
+    }
+}
+
+//Reads every row in salvage mode and turns the damage into issues.
+fn check_rows<R : Read>(sas : &mut Checked<R>, report : &mut ValidationReport){
+    loop{
+        match sas.read_line(){
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(er) => {
+                report.add(Check::Read, Some(sas.pages_read), format!("{er:?}"));
+                return;
+            }
+        }
+    }
+    for x in sas.damage_report().damaged.iter(){
+        let what = match x.rows_lost{
+            Some(n) => format!("{n} rows"),
+            None => "page".to_string(),
+        };
+        report.add(Check::Rows, Some(x.page), format!("{what} at bytes {}..{}: {}", x.start, x.end, x.error));
+    }
+    report.rows = sas.cur_row_in_file_idx;
+    //row_count may or may not include the deleted rows
+    if report.rows != sas.row_count && report.rows - sas.deleted_count != sas.row_count{
+        report.add(Check::RowCount, None, format!("{} rows, but the header says {}", report.rows, sas.row_count));
+    }
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "tokio")]
+use std::io::Cursor;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, ReadBuf};
+use sas::*;
+use sas::fixture::*;
+
+fn fixture(compression : Compression) -> Fixture {
+    let mut f = Fixture{compression, layout : Layout::Mix, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("x"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+    ];
+    for i in 0..1500 {
+        f.rows.push(vec![Cell::Num(i as f64 / 3.0), Cell::Str(format!("row{i}")), Cell::Num(i as f64)]);
+    }
+    f
+}
+
+fn sync_rows(f : &Fixture) -> Vec<Vec<SasVal>> {
+    SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap().map(|x| x.unwrap()).collect()
+}
+
+#[tokio::test]
+async fn same_rows_as_the_sync_reader() {
+    for compression in [Compression::None, Compression::Rle, Compression::Rdc] {
+        let f = fixture(compression);
+        let expected = format!("{:?}", sync_rows(&f));
+        let mut sas = AsyncSAS7bdat::new(Cursor::new(f.to_bytes())).await.unwrap();
+        assert_eq!(sas.row_count(), 1500);
+        let mut rows = Vec::new();
+        while sas.read_line().await.unwrap() {
+            rows.push(sas.row_vals().clone());
+        }
+        assert_eq!(format!("{rows:?}"), expected, "{compression:?}");
+
+        let sas = AsyncSAS7bdat::from_reader(Cursor::new(f.to_bytes())).await.unwrap();
+        let rows : Vec<Vec<SasVal>> = sas.into_stream().map(|x| x.unwrap()).collect().await;
+        assert_eq!(format!("{rows:?}"), expected, "{compression:?}");
+
+        let sas = AsyncSAS7bdat::from_reader(Cursor::new(f.to_bytes())).await.unwrap();
+        let batches : Vec<Vec<Vec<SasVal>>> = sas.into_batches(400).map(|x| x.unwrap()).collect().await;
+        assert_eq!(batches.iter().map(|x| x.len()).collect::<Vec<_>>(), [400, 400, 400, 300]);
+        assert_eq!(format!("{:?}", batches.concat()), expected, "{compression:?}");
+    }
+}
+
+//Fails once `len` bytes were read.
+struct Failing{
+    bytes : Cursor<Vec<u8>>,
+    len : u64,
+}
+
+impl AsyncRead for Failing{
+    fn poll_read(mut self : Pin<&mut Self>, cx : &mut Context<'_>, buf : &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        if self.bytes.position() >= self.len {
+            return Poll::Ready(Err(std::io::Error::other("connection reset")));
+        }
+        Pin::new(&mut self.bytes).poll_read(cx, buf)
+    }
+}
+
+#[tokio::test]
+async fn batches_before_an_error() {
+    let f = fixture(Compression::None);
+    let bytes = f.to_bytes();
+    let len = bytes.len() as u64 / 2;
+    let sas = AsyncSAS7bdat::from_reader(Failing{bytes : Cursor::new(bytes), len}).await.unwrap();
+    let batches : Vec<Result<Vec<Vec<SasVal>>, SasError>> = sas.into_batches(10_000).collect().await;
+    assert_eq!(batches.len(), 2);
+    let rows = batches[0].as_ref().unwrap();
+    assert!(!rows.is_empty() && rows.len() < 1500);
+    assert_eq!(format!("{rows:?}"), format!("{:?}", &sync_rows(&f)[..rows.len()]));
+    assert!(matches!(&batches[1], Err(SasError::Io(er)) if er.to_string() == "connection reset"));
+}
+
+//The header and page lengths of a 32 bit little-endian file are at 196 and
+//200. Neither reader allocates what a corrupt header asks for.
+#[tokio::test]
+async fn lengths_from_a_corrupt_header() {
+    let mut f = fixture(Compression::None);
+    f.u64 = false;
+    for (off, msg) in [(196, "Invalid header length"), (200, "Invalid page length")] {
+        let mut bytes = f.to_bytes();
+        bytes[off..off + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        let async_er = AsyncSAS7bdat::from_reader(Cursor::new(bytes.clone())).await.err();
+        for er in [async_er, SAS7bdat::from_reader(Cursor::new(bytes)).err()] {
+            match er {
+                Some(SasError::SasProperty(x)) => assert!(x.starts_with(msg), "{x}"),
+                er => panic!("{er:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::Cursor;
+use sas::*;
+use sas::fixture::*;
+
+fn fixture(compression : Compression, rows : usize) -> Fixture {
+    let mut f = Fixture{compression, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("x"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+        Column::numeric("t").with_format("TIME", 8, 0),
+    ];
+    for i in 0..rows {
+        let x = if i % 7 == 3 { f64::NAN } else { i as f64 * 0.5 };
+        f.rows.push(vec![
+            Cell::Num(x),
+            Cell::Str(if i % 5 == 0 { String::new() } else { format!("row{i}") }),
+            Cell::Num(i as f64),
+            Cell::Num(i as f64 * 60.5),
+            Cell::Num(90000.0 + i as f64),
+        ]);
+    }
+    f
+}
+
+fn open(bytes : &[u8]) -> SAS7bdat<Cursor<&[u8]>> {
+    SAS7bdat::from_reader(Cursor::new(bytes)).unwrap()
+}
+
+#[test]
+fn same_values_as_read_line() {
+    for compression in [Compression::None, Compression::Rle, Compression::Rdc] {
+        let bytes = fixture(compression, 300).to_bytes();
+        let rows : Vec<Vec<SasVal>> = open(&bytes).map(|x| x.unwrap()).collect();
+        let mut sas = open(&bytes);
+        let mut i = 0;
+        loop {
+            //batches cross page boundaries
+            let batch = sas.read_batch(64).unwrap();
+            if batch.is_empty() {
+                break;
+            }
+            assert_eq!(batch.columns.len(), 5);
+            for k in 0..batch.len() {
+                let row = &rows[i + k];
+                match (&batch.columns[0], &row[0]) {
+                    (BatchColumn::Numeric{values, validity}, SasVal::Numeric(x)) if x.is_nan() => {
+                        assert!(values[k].is_nan() && !validity.is_valid(k));
+                    }
+                    (BatchColumn::Numeric{values, validity}, SasVal::Numeric(x)) => {
+                        assert_eq!(values[k], *x);
+                        assert!(validity.is_valid(k));
+                    }
+                    val => panic!("{compression:?}, row {}: {val:?}", i + k),
+                }
+                match &row[1] {
+                    SasVal::Text(x) => assert_eq!(batch.columns[1].text(k).unwrap(), x.as_bytes()),
+                    val => panic!("{val:?}"),
+                }
+                match (&batch.columns[2], &row[2]) {
+                    (BatchColumn::Date{days, ..}, SasVal::Date(x)) => {
+                        assert_eq!(days[k] as i64, (*x - chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days());
+                    }
+                    val => panic!("{val:?}"),
+                }
+                match (&batch.columns[3], &row[3]) {
+                    (BatchColumn::DateTime{micros, ..}, SasVal::DateTime(x)) => {
+                        assert_eq!(micros[k].div_euclid(1_000_000), x.timestamp());
+                    }
+                    val => panic!("{val:?}"),
+                }
+                match &batch.columns[4] {
+                    BatchColumn::Time{micros, ..} => assert_eq!(micros[k], (90000 + (i + k) as i64) * 1_000_000),
+                    val => panic!("{val:?}"),
+                }
+            }
+            i += batch.len();
+        }
+        assert_eq!(i, 300, "{compression:?}");
+        assert!(sas.read_batch(10).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn epochs_and_validity() {
+    let columns = vec![Column::numeric("day").with_format("DATE", 9, 0), Column::numeric("x")];
+    let mut f = Fixture{columns, ..Fixture::default()};
+    for (day, x) in [(0.0, 1.0), (3653.0, f64::NAN), (f64::NAN, 2.0)] {
+        f.rows.push(vec![Cell::Num(day), Cell::Num(x)]);
+    }
+    let bytes = f.to_bytes();
+    let batch = open(&bytes).read_batch(100).unwrap();
+    assert_eq!(batch.len(), 3);
+    match &batch.columns[0] {
+        BatchColumn::Date{days, validity} => {
+            assert_eq!(&days[..2], &[-3653, 0]);
+            assert!(!validity.is_valid(2));
+            assert_eq!(validity.null_count(), 1);
+        }
+        val => panic!("{val:?}"),
+    }
+    match &batch.columns[1] {
+        BatchColumn::Numeric{validity, ..} => {
+            assert_eq!(validity.bits(), &[0b101]);
+            assert_eq!(validity.null_count(), 1);
+        }
+        val => panic!("{val:?}"),
+    }
+}
+
+#[test]
+fn mixed_with_read_line() {
+    let bytes = fixture(Compression::None, 20).to_bytes();
+    let mut sas = open(&bytes);
+    assert!(sas.read_line().unwrap());
+    let batch = sas.read_batch(5).unwrap();
+    assert_eq!(batch.columns[1].text(0).unwrap(), b"row1");
+    assert!(sas.read_line().unwrap());
+    assert!(matches!(&sas.row_vals[1], SasVal::Text(x) if x == "row6"));
+}
+
+#[test]
+fn raw_text() {
+    let mut f = Fixture{columns : vec![Column::character("name", 6)], ..Fixture::default()};
+    f.rows.push(vec![Cell::Bytes(b"caf\xe9".to_vec())]);
+    let bytes = f.to_bytes();
+    let mut sas = ReadOptions::default().decode_policy(DecodePolicy::Raw).read(Cursor::new(bytes)).unwrap();
+    let batch = sas.read_batch(1).unwrap();
+    assert_eq!(batch.columns[0].text(0).unwrap(), b"caf\xe9\x20\x20");
+}
+
+#[test]
+fn selected_columns() {
+    let bytes = fixture(Compression::Rle, 50).to_bytes();
+    let mut sas = open(&bytes);
+    let batch = sas.read_batch_columns(10, &[4, 1]).unwrap();
+    assert_eq!(batch.len(), 10);
+    assert!(matches!(&batch.columns[0], BatchColumn::Time{..}));
+    assert_eq!(batch.columns[1].text(9).unwrap(), b"row9");
+    assert!(sas.read_batch_columns(10, &[5]).is_err());
+}
+
+#[test]
+fn dates_out_of_range() {
+    let columns = vec![
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+    ];
+    let mut f = Fixture{columns, ..Fixture::default()};
+    for x in [1e300, -1e300, f64::INFINITY, 1e10] {
+        f.rows.push(vec![Cell::Num(x), Cell::Num(x * 1e6)]);
+    }
+    let bytes = f.to_bytes();
+    let batch = open(&bytes).read_batch(10).unwrap();
+    for col in &batch.columns {
+        match col {
+            BatchColumn::Date{validity, ..} | BatchColumn::DateTime{validity, ..} => assert_eq!(validity.null_count(), 4),
+            val => panic!("{val:?}"),
+        }
+    }
+    //the rows keep the numbers
+    let rows : Vec<Vec<SasVal>> = open(&bytes).map(|x| x.unwrap()).collect();
+    assert!(matches!(rows[0][0], SasVal::Numeric(x) if x == 1e300));
+    assert!(matches!(rows[1][1], SasVal::Numeric(x) if x == -1e306));
+}
+
+#[test]
+fn partial_batch_before_an_error() {
+    let mut bytes = fixture(Compression::None, 200).to_bytes();
+    bytes.truncate(bytes.len() - 100);
+    let mut sas = open(&bytes);
+    let batch = sas.read_batch(1000).unwrap();
+    assert!(batch.len() > 100 && batch.len() < 200, "{}", batch.len());
+    assert_eq!(batch.columns[1].text(1).unwrap(), b"row1");
+    assert!(matches!(sas.read_batch(1000), Err(SasError::Truncated(_))));
+}
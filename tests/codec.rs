@@ -0,0 +1,107 @@
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use sas::*;
+use sas::fixture::*;
+
+fn fixture() -> Fixture {
+    let mut f = Fixture{compression : Compression::Rle, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![Column::numeric("x"), Column::character("name", 12)];
+    for i in 0..500 {
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Str(format!("row{i}"))]);
+    }
+    f
+}
+
+fn rows<R : Read>(sas : SAS7bdat<R>) -> Vec<Vec<SasVal>> {
+    sas.map(|x| x.unwrap()).collect()
+}
+
+fn expected() -> String {
+    format!("{:?}", rows(SAS7bdat::from_reader(Cursor::new(fixture().to_bytes())).unwrap()))
+}
+
+fn temp_path(name : &str) -> PathBuf {
+    std::env::temp_dir().join(format!("sas_codec_{name}_{}", std::process::id()))
+}
+
+//Reads `data` both as a file and as a stream.
+fn check(name : &str, data : Vec<u8>) {
+    let path = temp_path(name);
+    std::fs::write(&path, &data).unwrap();
+    let from_file = SAS7bdat::open(&path).map(|x| format!("{:?}", rows(x)));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(from_file.unwrap(), expected(), "{name} file");
+    let from_stream = rows(ReadOptions::default().read(decompress(Cursor::new(data)).unwrap()).unwrap());
+    assert_eq!(format!("{from_stream:?}"), expected(), "{name} stream");
+}
+
+#[test]
+fn plain() {
+    check("plain", fixture().to_bytes());
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip() {
+    use std::io::Write;
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(&fixture().to_bytes()).unwrap();
+    check("gzip", enc.finish().unwrap());
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd() {
+    check("zstd", zstd::encode_all(Cursor::new(fixture().to_bytes()), 3).unwrap());
+}
+
+#[cfg(feature = "xz")]
+#[test]
+fn xz() {
+    use std::io::Write;
+    let mut enc = xz2::write::XzEncoder::new(Vec::new(), 6);
+    enc.write_all(&fixture().to_bytes()).unwrap();
+    check("xz", enc.finish().unwrap());
+}
+
+#[cfg(feature = "zip")]
+fn zip_archive(method : zip::CompressionMethod) -> Vec<u8> {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(method);
+    zip.start_file("readme.txt", options).unwrap();
+    zip.write_all(b"not a dataset").unwrap();
+    zip.start_file("data.sas7bdat", options).unwrap();
+    zip.write_all(&fixture().to_bytes()).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_members() {
+    for method in [zip::CompressionMethod::Stored, zip::CompressionMethod::Deflated] {
+        let path = temp_path(&format!("{method}.zip"));
+        std::fs::write(&path, zip_archive(method)).unwrap();
+        let only = SAS7bdat::open(&path).map(|x| format!("{:?}", rows(x)));
+        let named = SAS7bdat::open_member(&path, "data.sas7bdat").map(|x| format!("{:?}", rows(x)));
+        let missing = SAS7bdat::open_member(&path, "other.sas7bdat").is_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(only.unwrap(), expected(), "{method}");
+        assert_eq!(named.unwrap(), expected(), "{method}");
+        assert!(missing);
+    }
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_checksum() {
+    let mut data = zip_archive(zip::CompressionMethod::Stored);
+    let pos = data.windows(6).position(|x| x == b"row499").unwrap();
+    data[pos + 3] = b'5';
+    let path = temp_path("corrupt.zip");
+    std::fs::write(&path, data).unwrap();
+    let mut buf = Vec::new();
+    let read = open_reader(&path, None, 0).unwrap().read_to_end(&mut buf);
+    std::fs::remove_file(&path).unwrap();
+    assert!(read.is_err());
+}
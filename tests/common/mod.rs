@@ -0,0 +1,47 @@
+//Temp files and directories for the test files, each of which uses a part
+//of them.
+#![allow(dead_code)]
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+//A file or directory in the temp directory, removed when dropped.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(name : &str) -> TempPath {
+        TempPath(std::env::temp_dir().join(format!("sas_{}_{name}", std::process::id())))
+    }
+
+    //Writes `bytes` to a new temp file.
+    pub fn file(name : &str, bytes : &[u8]) -> TempPath {
+        let path = TempPath::new(name);
+        std::fs::write(&path.0, bytes).unwrap();
+        path
+    }
+
+    pub fn dir(name : &str) -> TempPath {
+        let path = TempPath::new(name);
+        std::fs::create_dir_all(&path.0).unwrap();
+        path
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = if self.0.is_dir() { std::fs::remove_dir_all(&self.0) } else { std::fs::remove_file(&self.0) };
+    }
+}
@@ -0,0 +1,316 @@
+use std::io::{Cursor, Read};
+use chrono::{Duration, NaiveDate};
+use sas::*;
+use sas::fixture::*;
+
+const WIDTHS : [usize; 6] = [3, 4, 5, 6, 7, 8];
+
+//Value read back from a numeric column of w bytes: SAS keeps the high order
+//bytes of the double.
+fn truncated(x : f64, w : usize) -> f64 {
+    if w == 8 {
+        return x;
+    }
+    f64::from_bits(x.to_bits() & !((1u64 << (8 * (8 - w))) - 1))
+}
+
+fn num(i : usize) -> f64 {
+    match i % 5 {
+        0 => i as f64,
+        1 => -(i as f64) * 1.25,
+        2 => 1.0 / (i as f64 + 3.0),
+        3 => 1e10 + i as f64,
+        _ => f64::NAN,
+    }
+}
+
+fn fixture(u64 : bool, endianness : Endianness, compression : Compression, layout : Layout, rows : usize) -> Fixture {
+    let mut f = Fixture{u64, endianness, compression, layout, ..Fixture::default()};
+    for w in WIDTHS {
+        f.columns.push(Column::new(&format!("n{w}"), ColumnKind::Numeric(w)));
+    }
+    f.columns.push(Column::character("text", 12).with_label("Some text"));
+    f.columns.push(Column::numeric("day").with_format("DATE", 9, 0));
+    for i in 0..rows {
+        let mut row : Vec<Cell> = WIDTHS.iter().map(|_| Cell::Num(num(i))).collect();
+        row.push(Cell::Str(format!("row {i}")));
+        row.push(Cell::Num(i as f64));
+        f.rows.push(row);
+    }
+    f
+}
+
+fn check(f : &Fixture, what : &str) {
+    let mut sas = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap_or_else(|er| panic!("{what}: {er:?}"));
+    assert_eq!(sas.row_count(), f.rows.len(), "{what}");
+    assert_eq!(sas.col_names().len(), f.columns.len(), "{what}");
+    assert_eq!(sas.col_label()[WIDTHS.len()], "Some text", "{what}");
+    let base = NaiveDate::from_ymd_opt(1960, 1, 1).unwrap();
+    let mut i = 0;
+    while sas.read_line().unwrap_or_else(|er| panic!("{what}, row {i}: {er:?}")) {
+        for (j, w) in WIDTHS.iter().enumerate() {
+            let expected = truncated(num(i), *w);
+            match sas.row_vals[j] {
+                SasVal::Numeric(x) if expected.is_nan() => assert!(x.is_nan(), "{what}, row {i}, width {w}"),
+                SasVal::Numeric(x) => assert_eq!(x, expected, "{what}, row {i}, width {w}"),
+                ref val => panic!("{what}, row {i}: {val:?}"),
+            }
+        }
+        match &sas.row_vals[WIDTHS.len()] {
+            SasVal::Text(x) => assert_eq!(x, &format!("row {i}"), "{what}"),
+            val => panic!("{what}, row {i}: {val:?}"),
+        }
+        match &sas.row_vals[WIDTHS.len() + 1] {
+            SasVal::Date(x) => assert_eq!(*x, base + Duration::days(i as i64), "{what}"),
+            val => panic!("{what}, row {i}: {val:?}"),
+        }
+        i += 1;
+    }
+    assert_eq!(i, f.rows.len(), "{what}");
+}
+
+#[test]
+fn bitness_endianness_compression() {
+    for u64 in [false, true] {
+        for endianness in [Endianness::Little, Endianness::Big] {
+            for compression in [Compression::None, Compression::Rle, Compression::Rdc] {
+                for layout in [Layout::Data, Layout::Mix] {
+                    let f = fixture(u64, endianness, compression, layout, 500);
+                    check(&f, &format!("u64 {u64}, {endianness:?}, {compression:?}, {layout:?}"));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn empty_dataset() {
+    for u64 in [false, true] {
+        for compression in [Compression::None, Compression::Rle, Compression::Rdc] {
+            let f = fixture(u64, Endianness::Little, compression, Layout::Data, 0);
+            check(&f, &format!("u64 {u64}, {compression:?}, no rows"));
+        }
+    }
+}
+
+#[test]
+fn small_pages() {
+    for u64 in [false, true] {
+        let mut f = fixture(u64, Endianness::Big, Compression::Rle, Layout::Data, 200);
+        f.page_len = 1024;
+        check(&f, &format!("u64 {u64}, 1024 byte pages"));
+    }
+}
+
+//A compressed row is a row even if its first bytes look like a subheader
+//signature. The RDC control bytes and the first literals of this row read as
+//the subheader-counts signature of a 32 bit big-endian file.
+#[test]
+fn compressed_row_looking_like_a_signature() {
+    let mut f = Fixture{
+        u64 : false,
+        endianness : Endianness::Big,
+        compression : Compression::Rdc,
+        ..Fixture::default()
+    };
+    f.columns.push(Column::character("raw", 64));
+    let mut raw = vec![0xFF, 0xFF, 1, 2, 3, 4, 5, 6];
+    for b in 7..13 {
+        raw.extend_from_slice(&[b; 4]);
+    }
+    raw.extend_from_slice(&[13, 14]);
+    raw.resize(64, b'x');
+    f.rows.push(vec![Cell::Bytes(raw.clone())]);
+    f.rows.push(vec![Cell::Str("plain".to_string())]);
+    let mut sas = ReadOptions::default().decode_policy(DecodePolicy::Raw).read(Cursor::new(f.to_bytes())).unwrap();
+    assert!(sas.read_line().unwrap());
+    assert!(matches!(&sas.row_vals[0], SasVal::Bytes(x) if *x == raw));
+    assert!(sas.read_line().unwrap());
+    assert!(!sas.read_line().unwrap());
+}
+
+#[test]
+fn runs_of_at_signs() {
+    for compression in [Compression::Rle, Compression::Rdc] {
+        let mut f = Fixture{compression, ..Fixture::default()};
+        f.columns.push(Column::character("at", 200));
+        for i in 0..50 {
+            f.rows.push(vec![Cell::Str(format!("{i}{}", "@".repeat(20 + i * 3)))]);
+        }
+        let mut sas = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap();
+        let mut i = 0;
+        while sas.read_line().unwrap() {
+            assert!(matches!(&sas.row_vals[0], SasVal::Text(x) if *x == format!("{i}{}", "@".repeat(20 + i * 3))));
+            i += 1;
+        }
+        assert_eq!(i, 50);
+    }
+}
+
+#[test]
+fn time_columns() {
+    let mut f = Fixture::default();
+    f.columns.push(Column::numeric("t").with_format("TIME", 8, 0));
+    for x in [0.0, 3661.0, 86399.0, 90000.0, f64::NAN] {
+        f.rows.push(vec![Cell::Num(x)]);
+    }
+    let rows : Vec<SasVal> = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap().map(|x| x.unwrap()[0].clone()).collect();
+    assert!(matches!(rows[1], SasVal::Time(x) if x.to_string() == "01:01:01"));
+    assert!(matches!(rows[2], SasVal::Time(x) if x.to_string() == "23:59:59"));
+    //a duration of more than a day
+    assert!(matches!(rows[3], SasVal::Numeric(x) if x == 90000.0));
+    assert!(matches!(rows[4], SasVal::Numeric(x) if x.is_nan()));
+}
+
+#[test]
+fn missing_dates() {
+    let mut f = Fixture::default();
+    f.columns.push(Column::numeric("day").with_format("DATE", 9, 0));
+    f.columns.push(Column::numeric("at").with_format("DATETIME", 19, 0));
+    //.B, as SAS writes it
+    let special = f64::from_bits(0xffff_0000_0000_0000 | ((!b'B' as u64) << 40));
+    for x in [0.0, f64::NAN, special] {
+        f.rows.push(vec![Cell::Num(x), Cell::Num(x)]);
+    }
+    let rows : Vec<Vec<SasVal>> = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap().map(|x| x.unwrap()).collect();
+    assert!(matches!(rows[0][0], SasVal::Date(x) if x.to_string() == "1960-01-01"));
+    assert!(matches!(rows[0][1], SasVal::DateTime(x) if x.timestamp() == -315_619_200));
+    //missing values are not read as the epoch
+    for val in &rows[1] {
+        assert!(matches!(val, SasVal::Numeric(x) if x.is_nan()), "{val:?}");
+        assert_eq!(val.missing_tag(), None);
+    }
+    for val in &rows[2] {
+        assert_eq!(val.missing_tag(), Some('B'), "{val:?}");
+    }
+}
+
+//A reader without Seek that hands out a few bytes at a time.
+struct Trickle{
+    bytes : Vec<u8>,
+    pos : usize,
+}
+
+impl Read for Trickle{
+    fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(7).min(self.bytes.len() - self.pos);
+        buf[..n].copy_from_slice(&self.bytes[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[test]
+fn non_seekable_reader() {
+    for u64 in [false, true] {
+        for compression in [Compression::None, Compression::Rdc] {
+            let f = fixture(u64, Endianness::Little, compression, Layout::Mix, 300);
+            let stream = Trickle{bytes : f.to_bytes(), pos : 0};
+            let rows : Vec<Vec<SasVal>> = SAS7bdat::from_reader(stream).unwrap().map(|x| x.unwrap()).collect();
+            let expected : Vec<Vec<SasVal>> = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap().map(|x| x.unwrap()).collect();
+            assert_eq!(rows.len(), 300);
+            assert_eq!(format!("{rows:?}"), format!("{expected:?}"), "u64 {u64}, {compression:?}");
+        }
+    }
+}
+
+//The creation and modification dates follow the alignment padding of 64 bit
+//headers.
+#[test]
+fn header_dates() {
+    let stamp = NaiveDate::from_ymd_opt(1960, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc()
+        + Duration::seconds(1_900_000_000);
+    for u64 in [false, true] {
+        let f = fixture(u64, Endianness::Big, Compression::None, Layout::Data, 1);
+        let sas = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap();
+        assert_eq!(sas.date_created(), Some(stamp), "u64 {u64}");
+        assert_eq!(sas.date_modified(), Some(stamp), "u64 {u64}");
+    }
+}
+
+//The second control byte of an RDC control word flags items 9 to 16. Here
+//the first 8 items are literals and the following ones runs.
+#[test]
+fn rdc_control_word_low_byte() {
+    let mut f = Fixture{compression : Compression::Rdc, ..Fixture::default()};
+    f.columns.push(Column::character("text", 64));
+    let mut text = "abcdefgh".to_string();
+    for c in 'i'..='p' {
+        text.extend(std::iter::repeat_n(c, 6));
+    }
+    f.rows.push(vec![Cell::Str(text.clone())]);
+    let compressed = rdc_compress(text.as_bytes());
+    assert_eq!(compressed[..2], [0x00, 0xFF]);
+    let rows : Vec<Vec<SasVal>> = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap().map(|x| x.unwrap()).collect();
+    assert!(matches!(&rows[0][0], SasVal::Text(x) if *x == text));
+}
+
+//Fails after the header and the first pages.
+struct Failing(Cursor<Vec<u8>>);
+
+impl Read for Failing{
+    fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+        if self.0.position() >= 8192 {
+            return Err(std::io::Error::other("disk on fire"));
+        }
+        let n = buf.len().min(8192 - self.0.position() as usize);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+#[test]
+fn read_errors_are_passed_on() {
+    let mut f = fixture(false, Endianness::Little, Compression::None, Layout::Data, 2000);
+    f.page_len = 1024;
+    let res : Result<Vec<Vec<SasVal>>, SasError> = SAS7bdat::from_reader(Failing(Cursor::new(f.to_bytes()))).unwrap().collect();
+    match res {
+        Err(SasError::Io(er)) => assert_eq!(er.to_string(), "disk on fire"),
+        res => panic!("{:?}", res.map(|x| x.len())),
+    }
+}
+
+//Numeric cells of fewer than 8 bytes hold the high order bytes of the
+//double, in file order for big-endian files. The cells are patched by hand
+//rather than written by the generator.
+#[test]
+fn short_numerics_big_endian() {
+    let mut f = Fixture{u64 : false, endianness : Endianness::Big, ..Fixture::default()};
+    f.columns.push(Column::new("n3", ColumnKind::Numeric(3)));
+    f.columns.push(Column::new("n5", ColumnKind::Numeric(5)));
+    f.rows.push(vec![Cell::Num(1234.5), Cell::Num(1234.5)]);
+    let mut bytes = f.to_bytes();
+    //1234.5 is 0x4093_4A00_0000_0000, both cells lie next to each other
+    let pos = bytes.windows(8).position(|x| x == [0x40, 0x93, 0x4A, 0x40, 0x93, 0x4A, 0x00, 0x00]).unwrap();
+    //-100.0 and 3.140625
+    bytes[pos..pos + 8].copy_from_slice(&[0xC0, 0x59, 0x00, 0x40, 0x09, 0x20, 0x00, 0x00]);
+    let rows : Vec<Vec<SasVal>> = SAS7bdat::from_reader(Cursor::new(bytes)).unwrap().map(|x| x.unwrap()).collect();
+    assert!(matches!(rows[0][0], SasVal::Numeric(x) if x == -100.0), "{:?}", rows[0]);
+    assert!(matches!(rows[0][1], SasVal::Numeric(x) if x == 3.140625), "{:?}", rows[0]);
+}
+
+#[test]
+fn temporal_formats() {
+    let mut f = Fixture::default();
+    let dates = ["DATE", "DDMMYY", "MMDDYY", "YYMMDD", "E8601DA", "MONYY", "YEAR", "mmddyy"];
+    for name in dates {
+        f.columns.push(Column::numeric(&format!("d_{name}")).with_format(name, 0, 0));
+    }
+    f.columns.push(Column::numeric("dt").with_format("DATETIME", 0, 0));
+    f.columns.push(Column::numeric("e8601dt").with_format("E8601DT", 0, 0));
+    f.columns.push(Column::numeric("t").with_format("E8601TM", 0, 0));
+    f.columns.push(Column::numeric("n").with_format("COMMA", 12, 2));
+    let mut row = vec![Cell::Num(366.0); dates.len()];
+    row.extend([Cell::Num(86400.0 + 3661.0), Cell::Num(86400.0 + 3661.0), Cell::Num(3661.0), Cell::Num(366.0)]);
+    f.rows.push(row);
+    let rows : Vec<Vec<SasVal>> = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap().map(|x| x.unwrap()).collect();
+    let day = NaiveDate::from_ymd_opt(1961, 1, 1).unwrap();
+    for (j, name) in dates.iter().enumerate() {
+        assert!(matches!(rows[0][j], SasVal::Date(x) if x == day), "{name}: {:?}", rows[0][j]);
+    }
+    let j = dates.len();
+    for val in &rows[0][j..j + 2] {
+        assert!(matches!(val, SasVal::DateTime(x) if x.to_string() == "1960-01-02 01:01:01 UTC"), "{val:?}");
+    }
+    assert!(matches!(rows[0][j + 2], SasVal::Time(x) if x.to_string() == "01:01:01"));
+    assert!(matches!(rows[0][j + 3], SasVal::Numeric(x) if x == 366.0));
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "datafusion")]
+use datafusion::arrow::array::{Array, AsArray, Date32Array, Float64Array};
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::prelude::SessionContext;
+use sas::ReadOptions;
+use sas::fixture::*;
+
+mod common;
+use common::TempPath;
+
+fn fixture(first : usize, rows : usize, extra : bool) -> Fixture {
+    let mut f = Fixture{compression : Compression::Rle, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("id").with_label("Subject"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+    ];
+    if extra {
+        f.columns.push(Column::numeric("score"));
+    }
+    for i in first..first + rows {
+        let mut row = vec![Cell::Num(i as f64), Cell::Str(format!("row{i}")), Cell::Num(i as f64), Cell::Num(0.0)];
+        if extra {
+            row.push(Cell::Num(if i % 2 == 0 { f64::NAN } else { 1.0 }));
+        }
+        f.rows.push(row);
+    }
+    f
+}
+
+//Two files, the second with a score column.
+fn dir(name : &str) -> TempPath {
+    let dir = TempPath::dir(&format!("datafusion_{name}"));
+    std::fs::write(dir.join("a.sas7bdat"), fixture(0, 300, false).to_bytes()).unwrap();
+    std::fs::write(dir.join("b.sas7bdat"), fixture(300, 200, true).to_bytes()).unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn several_files_as_one_table() {
+    let dir = dir("table");
+    let ctx = SessionContext::new();
+    sas::datafusion::register_sas7bdat(&ctx, "t", &format!("{}/", dir.display()), ReadOptions::default()).await.unwrap();
+    let df = ctx.table("t").await.unwrap();
+    let schema = df.schema().as_arrow().clone();
+    assert_eq!(schema.field_with_name("id").unwrap().metadata()["label"], "Subject");
+    assert_eq!(schema.field_with_name("day").unwrap().data_type(), &DataType::Date32);
+    assert_eq!(schema.field_with_name("at").unwrap().data_type(), &DataType::Timestamp(TimeUnit::Microsecond, None));
+
+    let batches = ctx.sql("SELECT count(*), sum(id), count(score) FROM t").await.unwrap().collect().await.unwrap();
+    let row = &batches[0];
+    assert_eq!(row.column(0).as_primitive::<datafusion::arrow::datatypes::Int64Type>().value(0), 500);
+    assert_eq!(row.column(1).as_any().downcast_ref::<Float64Array>().unwrap().value(0), (0..500).sum::<usize>() as f64);
+    //the first file has no score column, half of the second are missing
+    assert_eq!(row.column(2).as_primitive::<datafusion::arrow::datatypes::Int64Type>().value(0), 100);
+
+    //no column is read for count(*)
+    let batches = ctx.sql("SELECT count(*) FROM t").await.unwrap().collect().await.unwrap();
+    assert_eq!(batches[0].column(0).as_primitive::<datafusion::arrow::datatypes::Int64Type>().value(0), 500);
+
+    let batches = ctx.sql("SELECT day FROM t WHERE name = 'row3'").await.unwrap().collect().await.unwrap();
+    let day = batches.iter().find(|x| x.num_rows() > 0).unwrap().column(0).as_any().downcast_ref::<Date32Array>().unwrap().value(0);
+    assert_eq!(day, 3 - 3653);
+}
+
+#[tokio::test]
+async fn url_tables_projection_and_limit() {
+    let dir = dir("url");
+    let ctx = SessionContext::new().enable_url_table();
+    sas::datafusion::register_format(&ctx, ReadOptions::default()).unwrap();
+    let sql = format!("SELECT name, id FROM '{}' LIMIT 7", dir.join("a.sas7bdat").display());
+    let batches = ctx.sql(&sql).await.unwrap().collect().await.unwrap();
+    assert_eq!(batches.iter().map(|x| x.num_rows()).sum::<usize>(), 7);
+    assert_eq!(batches[0].num_columns(), 2);
+    assert_eq!(batches[0].column(0).as_string::<i32>().value(0), "row0");
+
+    let sql = format!("SELECT a.id FROM '{0}/a.sas7bdat' a JOIN '{0}/*.sas7bdat' b ON a.id = b.id", dir.display());
+    let batches = ctx.sql(&sql).await.unwrap().collect().await.unwrap();
+    assert_eq!(batches.iter().map(|x| x.num_rows()).sum::<usize>(), 300);
+    assert!(batches.iter().all(|x| x.column(0).null_count() == 0));
+}
+
+#[tokio::test]
+async fn truncated_file_fails() {
+    let dir = TempPath::dir("datafusion_truncated");
+    let mut bytes = fixture(0, 300, false).to_bytes();
+    bytes.truncate(bytes.len() - 100);
+    std::fs::write(dir.join("a.sas7bdat"), bytes).unwrap();
+    let ctx = SessionContext::new();
+    sas::datafusion::register_sas7bdat(&ctx, "t", &format!("{}/", dir.display()), ReadOptions::default()).await.unwrap();
+    let er = ctx.sql("SELECT count(*) FROM t").await.unwrap().collect().await.unwrap_err();
+    assert!(er.to_string().contains("Truncated"), "{er}");
+}
+
+mod ranged {
+    use std::fmt;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use datafusion::object_store::memory::InMemory;
+    use datafusion::object_store::path::Path;
+    use datafusion::object_store::*;
+    use futures_util::stream::BoxStream;
+    use super::*;
+
+    //Counts the bytes fetched from an in-memory store.
+    #[derive(Debug, Default)]
+    struct Counting {
+        inner : InMemory,
+        fetched : AtomicU64,
+    }
+
+    impl fmt::Display for Counting {
+        fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Counting({})", self.inner)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ObjectStore for Counting {
+        async fn put_opts(&self, location : &Path, payload : PutPayload, opts : PutOptions) -> Result<PutResult> {
+            self.inner.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(&self, location : &Path, opts : PutMultipartOptions) -> Result<Box<dyn MultipartUpload>> {
+            self.inner.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(&self, location : &Path, options : GetOptions) -> Result<GetResult> {
+            let head = options.head;
+            let res = self.inner.get_opts(location, options).await?;
+            if !head {
+                self.fetched.fetch_add(res.range.end - res.range.start, Ordering::Relaxed);
+            }
+            Ok(res)
+        }
+
+        fn delete_stream(&self, locations : BoxStream<'static, Result<Path>>) -> BoxStream<'static, Result<Path>> {
+            self.inner.delete_stream(locations)
+        }
+
+        fn list(&self, prefix : Option<&Path>) -> BoxStream<'static, Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(&self, prefix : Option<&Path>) -> Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy_opts(&self, from : &Path, to : &Path, options : CopyOptions) -> Result<()> {
+            self.inner.copy_opts(from, to, options).await
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn limit_fetches_the_first_pages() {
+        let bytes = fixture(0, 200_000, false).to_bytes();
+        let store = Arc::new(Counting::default());
+        store.put(&Path::from("big.sas7bdat"), bytes.clone().into()).await.unwrap();
+        let ctx = SessionContext::new();
+        let url = datafusion::execution::object_store::ObjectStoreUrl::parse("memory://").unwrap();
+        ctx.register_object_store(url.as_ref(), Arc::clone(&store) as Arc<dyn ObjectStore>);
+        sas::datafusion::register_sas7bdat(&ctx, "t", "memory:///big.sas7bdat", ReadOptions::default()).await.unwrap();
+        let batches = ctx.sql("SELECT name FROM t LIMIT 5").await.unwrap().collect().await.unwrap();
+        assert_eq!(batches.iter().map(|x| x.num_rows()).sum::<usize>(), 5);
+        let fetched = store.fetched.load(Ordering::Relaxed);
+        assert!(fetched < bytes.len() as u64 / 2, "{fetched} of {}", bytes.len());
+    }
+}
@@ -0,0 +1,199 @@
+use std::io::Cursor;
+use sas::*;
+use sas::fixture::*;
+
+mod common;
+
+fn fixture(encoding : u8) -> Fixture {
+    let mut f = Fixture{encoding, ..Fixture::default()};
+    f.columns = vec![Column::character("name", 8)];
+    f.rows.push(vec![Cell::Bytes(b"caf\xe9".to_vec())]);
+    f
+}
+
+fn first_cell(sas : &mut SAS7bdat<Cursor<Vec<u8>>>) -> SasVal {
+    assert!(sas.read_line().unwrap());
+    sas.row_vals[0].clone()
+}
+
+#[test]
+fn id_table() {
+    for (id, name) in [(20, "utf-8"), (28, "us-ascii"), (29, "latin1"), (40, "latin9"), (62, "wlatin1"), (138, "shift-jis"), (205, "gb18030")] {
+        let enc = Encoding::from_id(id).unwrap_or_else(|| panic!("{id}"));
+        assert_eq!(enc.name(), name);
+        assert!(enc.is_supported(), "{name}");
+    }
+    //the session encoding of the writing SAS and ids missing from the table
+    for id in [0, 1, 204, 255] {
+        assert!(Encoding::from_id(id).is_none(), "{id}");
+    }
+    assert!(!Encoding::from_id(168).unwrap().is_supported());
+    //every id is found again by its name
+    for id in 1..=u8::MAX {
+        if let Some(enc) = Encoding::from_id(id) {
+            assert_eq!(Encoding::from_name(enc.name()), Some(enc), "{id}");
+        }
+    }
+}
+
+#[test]
+fn equal_by_decoder() {
+    let named = |x : &str| Encoding::from_name(x).unwrap_or_else(|| panic!("{x}"));
+    assert_eq!(named("UTF8"), Encoding::UTF_8);
+    assert_eq!(named("windows-1252"), Encoding::WLATIN1);
+    assert_eq!(named("windows-1252").name(), "windows-1252");
+    //latin1 decodes 0x80 to 0x9f as control characters, windows-1252 does not
+    assert_ne!(named("latin1"), Encoding::WLATIN1);
+    assert_ne!(named("ebcdic1047"), named("ebcdic1140"));
+    assert_ne!(Encoding::from_id(168).unwrap(), Encoding::from_id(169).unwrap());
+    //a guess against a default named by its label is the same encoding
+    let bytes = texts_file(0, &[b"caf\xe9", b"M\xfcller"]);
+    let mut sas = ReadOptions::default().default_encoding(named("cp1252")).read(Cursor::new(bytes)).unwrap();
+    assert_eq!(sas.detect_encoding(100).unwrap().encoding, Encoding::WLATIN1);
+}
+
+#[test]
+fn default_encoding_fallback() {
+    let pcoem437 = Encoding::from_name("pcoem437").unwrap();
+    for id in [0, 204, 1] {
+        let bytes = fixture(id).to_bytes();
+        let mut sas = SAS7bdat::from_reader(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(sas.encoding(), Encoding::WLATIN1);
+        assert_eq!(sas.encoding_id(), id);
+        assert!(matches!(first_cell(&mut sas), SasVal::Text(x) if x == "café"));
+
+        let mut sas = ReadOptions::default().default_encoding(pcoem437).read(Cursor::new(bytes)).unwrap();
+        assert_eq!(sas.encoding(), pcoem437);
+        assert!(matches!(first_cell(&mut sas), SasVal::Text(x) if x == "cafΘ"), "{id}");
+    }
+    //the default does not apply to known ids
+    let sas = ReadOptions::default().default_encoding(pcoem437).read(Cursor::new(fixture(20).to_bytes())).unwrap();
+    assert_eq!(sas.encoding(), Encoding::UTF_8);
+    assert_eq!(sas.encoding_id(), 20);
+}
+
+#[test]
+fn unsupported_encoding() {
+    let bytes = fixture(168).to_bytes();
+    match SAS7bdat::from_reader(Cursor::new(bytes.clone())) {
+        Err(SasError::SasProperty(msg)) => assert!(msg.contains("iso-2022-kr"), "{msg}"),
+        Err(er) => panic!("{er:?}"),
+        Ok(_) => panic!("read an unsupported encoding"),
+    }
+    let mut sas = ReadOptions::default().encoding(Encoding::WLATIN1).read(Cursor::new(bytes)).unwrap();
+    assert!(matches!(first_cell(&mut sas), SasVal::Text(x) if x == "café"));
+}
+
+//A utf-8 file with an invalid byte in the first cell of "bad" and an "é"
+//cut off at the width of "cut", then a valid row.
+fn malformed(bad : bool) -> Vec<u8> {
+    let mut f = Fixture{encoding : 20, ..Fixture::default()};
+    f.columns = vec![Column::character("bad", 8), Column::character("cut", 4)];
+    f.rows.push(vec![Cell::Bytes(b"a\xffb".to_vec()), Cell::Bytes(b"abc\xc3".to_vec())]);
+    f.rows.push(vec![Cell::Str("ok".into()), Cell::Str("né".into())]);
+    if !bad {
+        f.columns.remove(0);
+        f.rows.iter_mut().for_each(|x| { x.remove(0); });
+    }
+    f.to_bytes()
+}
+
+fn read_rows(sas : &mut SAS7bdat<Cursor<Vec<u8>>>) -> Result<Vec<Vec<SasVal>>, SasError> {
+    let mut rows = Vec::new();
+    while sas.read_line()? {
+        rows.push(sas.row_vals.clone());
+    }
+    Ok(rows)
+}
+
+fn texts(row : &[SasVal]) -> Vec<&str> {
+    row.iter().map(|x| match x {
+        SasVal::Text(x) => x.as_str(),
+        val => panic!("{val:?}"),
+    }).collect()
+}
+
+#[test]
+fn decode_policies() {
+    let read = |policy, bad| ReadOptions::default().decode_policy(policy).read(Cursor::new(malformed(bad))).unwrap();
+    let mut sas = read(DecodePolicy::Strict, true);
+    assert!(matches!(read_rows(&mut sas), Err(SasError::ByteConversion)));
+    assert_eq!(sas.decode_errors(), [1, 0]);
+
+    let mut sas = read(DecodePolicy::Replace, true);
+    let rows = read_rows(&mut sas).unwrap();
+    assert_eq!(texts(&rows[0]), ["a\u{fffd}b", "abc\u{fffd}"]);
+    assert_eq!(texts(&rows[1]), ["ok", "né"]);
+    assert_eq!(sas.decode_errors(), [1, 1]);
+
+    //only a character cut off at the end is dropped
+    let mut sas = read(DecodePolicy::TrimIncomplete, true);
+    assert!(matches!(read_rows(&mut sas), Err(SasError::ByteConversion)));
+    let mut sas = read(DecodePolicy::TrimIncomplete, false);
+    let rows = read_rows(&mut sas).unwrap();
+    assert_eq!(texts(&rows[0]), ["abc"]);
+    assert_eq!(texts(&rows[1]), ["né"]);
+    assert_eq!(sas.decode_errors(), [1]);
+
+    let mut sas = read(DecodePolicy::Raw, true);
+    let rows = read_rows(&mut sas).unwrap();
+    assert!(matches!(&rows[0][0], SasVal::Bytes(x) if x.starts_with(b"a\xffb")), "{:?}", rows[0]);
+    assert_eq!(sas.decode_errors(), [0, 0]);
+}
+
+fn texts_file(encoding : u8, cells : &[&[u8]]) -> Vec<u8> {
+    let mut f = Fixture{encoding, ..Fixture::default()};
+    f.columns = vec![Column::numeric("x"), Column::character("name", 12)];
+    for (i, x) in cells.iter().enumerate() {
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Bytes(x.to_vec())]);
+    }
+    f.to_bytes()
+}
+
+fn guess(encoding : u8, cells : &[&[u8]]) -> EncodingGuess {
+    SAS7bdat::from_reader(Cursor::new(texts_file(encoding, cells))).unwrap().detect_encoding(100).unwrap()
+}
+
+#[test]
+fn detection_confidence() {
+    //one half more for every multi-byte character
+    let utf8 = guess(62, &["José".as_bytes(), "Müller".as_bytes(), b"plain", "Zoë".as_bytes()]);
+    assert_eq!(utf8.encoding, Encoding::UTF_8);
+    assert_eq!(utf8.samples, 3);
+    assert!((utf8.confidence - 0.875).abs() < 1e-9, "{}", utf8.confidence);
+    let one = guess(62, &["José".as_bytes()]);
+    assert!((one.confidence - 0.5).abs() < 1e-9, "{}", one.confidence);
+    //ascii says nothing about the encoding
+    let ascii = guess(62, &[b"plain", b"text"]);
+    assert_eq!(ascii.encoding, Encoding::WLATIN1);
+    assert_eq!((ascii.confidence, ascii.samples), (0.0, 0));
+}
+
+#[test]
+fn detection_against_the_header() {
+    //labelled utf-8, written as wlatin1 with a euro sign latin1 lacks
+    let guessed = guess(20, &[b"caf\xe9", b"M\xfcller", b"\x80 5"]);
+    assert_eq!(guessed.encoding, Encoding::WLATIN1);
+    assert!((guessed.confidence - 0.875).abs() < 1e-9, "{}", guessed.confidence);
+    //the header is kept when it reads the text as well as the others
+    let guessed = guess(29, &[b"caf\xe9", b"M\xfcller"]);
+    assert_eq!(guessed.encoding.name(), "latin1");
+}
+
+//Runs `sas info` on a file, returning its stderr.
+fn info_warnings(name : &str, bytes : &[u8]) -> String {
+    let path = common::TempPath::file(&format!("encoding_{name}.sas7bdat"), bytes);
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_sas")).arg("info").arg(&*path).output().unwrap();
+    assert!(out.status.success(), "{out:?}");
+    String::from_utf8(out.stderr).unwrap()
+}
+
+#[test]
+fn info_warns_about_the_header() {
+    let cells : &[&[u8]] = &["José".as_bytes(), "Müller".as_bytes()];
+    let warning = info_warnings("lying", &texts_file(62, cells));
+    assert!(warning.contains("warning: the header names the encoding wlatin1, but the text looks like utf-8 (confidence 0.75)"), "{warning}");
+    assert_eq!(info_warnings("honest", &texts_file(20, cells)), "");
+    //too little evidence
+    assert_eq!(info_warnings("ascii", &texts_file(62, &[b"plain"])), "");
+}
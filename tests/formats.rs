@@ -0,0 +1,141 @@
+use std::io::Cursor;
+use chrono::{NaiveDate, NaiveTime};
+use sas::*;
+use sas::fixture::*;
+
+fn show(x : f64, fmt : &str) -> String {
+    SasVal::Numeric(x).format(&ColumnFormat::parse(fmt).unwrap())
+}
+
+#[test]
+fn parse() {
+    assert_eq!(ColumnFormat::parse("COMMA12.2"), Some(ColumnFormat::new("COMMA", 12, 2)));
+    assert_eq!(ColumnFormat::parse("z5."), Some(ColumnFormat::new("Z", 5, 0)));
+    assert_eq!(ColumnFormat::parse("8.2"), Some(ColumnFormat::new("", 8, 2)));
+    assert_eq!(ColumnFormat::parse("$CHAR10."), Some(ColumnFormat::new("$CHAR", 10, 0)));
+    assert_eq!(ColumnFormat::parse("E8601DA10."), Some(ColumnFormat::new("E8601DA", 10, 0)));
+    assert_eq!(ColumnFormat::parse("BEST"), Some(ColumnFormat::new("BEST", 0, 0)));
+    assert_eq!(ColumnFormat::parse("."), None);
+    assert_eq!(ColumnFormat::parse("COMMA12.x"), None);
+    assert_eq!(ColumnFormat::new("DOLLAR", 10, 2).to_string(), "DOLLAR10.2");
+    assert_eq!(ColumnFormat::new("DATE", 0, 0).to_string(), "DATE.");
+}
+
+#[test]
+fn numeric_formats() {
+    assert_eq!(show(1234.567, "COMMA12.2"), "1,234.57");
+    assert_eq!(show(-1234567.0, "COMMA12."), "-1,234,567");
+    assert_eq!(show(42.0, "Z5."), "00042");
+    assert_eq!(show(-4.25, "Z6.1"), "-004.3");
+    assert_eq!(show(0.123, "PERCENT8.1"), "12.3%");
+    assert_eq!(show(-0.5, "PERCENT8.1"), "(50.0%)");
+    assert_eq!(show(1234.5, "DOLLAR10.2"), "$1,234.50");
+    assert_eq!(show(5.6789, "8.2"), "5.68");
+    assert_eq!(show(-0.001, "8.2"), "0.00");
+    assert_eq!(show(1.0 / 3.0, "BEST12."), "0.3333333333");
+    assert_eq!(show(42.0, "BEST12."), "42");
+    assert_eq!(show(123456789012345.0, "BEST12."), "1.2345679E14");
+    assert_eq!(show(1.5e-12, "BEST12."), "1.5E-12");
+    assert_eq!(show(0.1 + 0.2, "BEST."), "0.3");
+    //too wide for the format, shown as BEST
+    assert_eq!(show(123456789.0, "COMMA8."), "1.2346E8");
+    assert_eq!(show(12.5, "UNKNOWN."), "12.5");
+    assert_eq!(show(f64::NAN, "COMMA12.2"), ".");
+    assert_eq!(show(f64::from_bits(0xffff_0000_0000_0000 | ((!b'B' as u64) << 40)), "8.2"), ".B");
+}
+
+#[test]
+fn dates_and_times() {
+    let day = SasVal::Date(NaiveDate::from_ymd_opt(2024, 3, 7).unwrap());
+    let fmt = |x : &str| ColumnFormat::parse(x).unwrap();
+    assert_eq!(day.format(&fmt("DATE9.")), "07MAR2024");
+    assert_eq!(day.format(&fmt("DATE.")), "07MAR24");
+    assert_eq!(day.format(&fmt("DATE11.")), "07-MAR-2024");
+    assert_eq!(day.format(&fmt("DDMMYY10.")), "07/03/2024");
+    assert_eq!(day.format(&fmt("MMDDYY8.")), "03/07/24");
+    assert_eq!(day.format(&fmt("YYMMDD10.")), "2024-03-07");
+    assert_eq!(day.format(&fmt("MONYY7.")), "MAR2024");
+    assert_eq!(day.format(&fmt("YEAR4.")), "2024");
+    assert_eq!(day.format(&fmt("BEST.")), "23442");
+    //days since 1960 in a numeric column
+    assert_eq!(show(23442.0, "E8601DA."), "2024-03-07");
+    assert_eq!(show(0.0, "DATE2."), "**");
+
+    let secs = 23442.0 * 86400.0 + 13.0 * 3600.0 + 5.0 * 60.0 + 9.25;
+    assert_eq!(show(secs, "DATETIME."), "07MAR24:13:05:09");
+    assert_eq!(show(secs, "DATETIME20."), "07MAR2024:13:05:09");
+    assert_eq!(show(secs, "DATETIME22.2"), "07MAR2024:13:05:09.25");
+    assert_eq!(show(secs, "E8601DT."), "2024-03-07T13:05:09");
+    assert_eq!(show(secs, "TOD."), "13:05:09");
+
+    let t = SasVal::Time(NaiveTime::from_hms_opt(9, 5, 30).unwrap());
+    assert_eq!(t.format(&fmt("TIME8.")), "9:05:30");
+    assert_eq!(t.format(&fmt("TIME5.")), "9:05");
+    assert_eq!(t.format(&fmt("HHMM.")), "9:06");
+    assert_eq!(t.format(&fmt("E8601TM.")), "09:05:30");
+    assert_eq!(show(90000.0, "TIME8."), "25:00:00");
+}
+
+#[test]
+fn character_formats() {
+    let x = SasVal::Text("hello world".into());
+    assert_eq!(x.format(&ColumnFormat::parse("$5.").unwrap()), "hello");
+    assert_eq!(x.format(&ColumnFormat::parse("$UPCASE.").unwrap()), "HELLO WORLD");
+    assert_eq!(x.format(&ColumnFormat::parse("$CHAR20.").unwrap()), "hello world");
+}
+
+//No SAS file with a COMMA12.2 column is at hand, so the fixture is written
+//without widths and they are patched into its format subheaders, where
+//ReadStat reads them: 2 bytes each, after the signature and two integers.
+#[test]
+fn widths_from_file() {
+    for (u64, endianness) in [(true, Endianness::Little), (false, Endianness::Big)] {
+        let mut f = Fixture{u64, endianness, page_len : 1024, ..Fixture::default()};
+        f.columns = vec![
+            Column::numeric("amount").with_format("COMMA", 0, 0),
+            Column::numeric("day").with_format("DATE", 0, 0),
+            Column::character("name", 8).with_format("$CHAR", 0, 0),
+            Column::numeric("odd").with_format("BEST", 0, 0),
+        ];
+        f.rows.push(vec![Cell::Num(1234.567), Cell::Num(0.0), Cell::Str("abcdef".into()), Cell::Num(1.0)]);
+        let mut bytes = f.to_bytes();
+
+        let int_len = if u64 { 8 } else { 4 };
+        //-1026, the signature of the format subheader
+        let mut sig = vec![0xff; int_len];
+        sig[0] = 0xfe;
+        sig[1] = 0xfb;
+        if endianness == Endianness::Big {
+            sig.reverse();
+        }
+        let subs : Vec<usize> = bytes.windows(int_len).enumerate().filter(|(_, x)| *x == sig.as_slice()).map(|(i, _)| i).collect();
+        assert_eq!(subs.len(), 4);
+        let patch = |bytes : &mut Vec<u8>, off : usize, width : u16, decimals : u16| {
+            let (width, decimals) = match endianness {
+                Endianness::Big => (width.to_be_bytes(), decimals.to_be_bytes()),
+                Endianness::Little => (width.to_le_bytes(), decimals.to_le_bytes()),
+            };
+            bytes[off + 3 * int_len..off + 3 * int_len + 2].copy_from_slice(&width);
+            bytes[off + 3 * int_len + 2..off + 3 * int_len + 4].copy_from_slice(&decimals);
+        };
+        //the column of each subheader, found from a marker width
+        for (k, off) in subs.iter().enumerate() {
+            patch(&mut bytes, *off, 100 + k as u16, 0);
+        }
+        let marked = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap().col_format_specs().clone();
+        let wanted = [(12, 2), (9, 0), (3, 0), (0x8000, 0xffff)];
+        for (j, spec) in marked.iter().enumerate() {
+            let (width, decimals) = wanted[j];
+            patch(&mut bytes, subs[usize::from(spec.width - 100)], width, decimals);
+        }
+
+        let mut sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+        let specs = sas.col_format_specs().clone();
+        assert_eq!(specs[0], ColumnFormat::new("COMMA", 12, 2));
+        //widths past i16::MAX do not fail
+        assert_eq!(specs[3], ColumnFormat::new("BEST", 0x8000, 0xffff));
+        assert!(sas.read_line().unwrap());
+        let shown : Vec<String> = sas.row_vals.iter().zip(specs.iter()).take(3).map(|(x, f)| x.format(f)).collect();
+        assert_eq!(shown, ["1,234.57", "01JAN1960", "abc"], "{endianness:?}");
+    }
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "json")]
+use std::io::Cursor;
+use serde_json::{json, Value};
+use sas::*;
+use sas::fixture::*;
+use sas::json::JsonOptions;
+
+//the complemented letter in the third byte of a NaN, as SAS writes .A
+fn special(tag : u8) -> f64 {
+    f64::from_bits(0xffff_0000_0000_0000 | ((!tag as u64) << 40))
+}
+
+fn fixture() -> Vec<u8> {
+    let mut f = Fixture{compression : Compression::Rle, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("x").with_label("Some number"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+        Column::numeric("t").with_format("TIME", 8, 0),
+    ];
+    f.rows.push(vec![Cell::Num(1.5), Cell::Str("one".into()), Cell::Num(0.0), Cell::Num(90.25), Cell::Num(3661.0)]);
+    f.rows.push(vec![Cell::Num(f64::NAN), Cell::Str(String::new()), Cell::Num(f64::NAN), Cell::Num(f64::NAN), Cell::Num(f64::NAN)]);
+    f.rows.push(vec![Cell::Num(special(b'A')), Cell::Str("three".into()), Cell::Num(special(b'_')), Cell::Num(-1.0), Cell::Num(0.0)]);
+    f.to_bytes()
+}
+
+fn lines(options : JsonOptions) -> Vec<Value> {
+    let bytes = fixture();
+    let mut sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(options.write_lines(&mut sas, &mut out).unwrap(), 3);
+    String::from_utf8(out).unwrap().lines().map(|x| serde_json::from_str(x).unwrap()).collect()
+}
+
+#[test]
+fn json_lines() {
+    let rows = lines(JsonOptions::default());
+    assert_eq!(rows[0], json!({"x" : 1.5, "name" : "one", "day" : "1960-01-01", "at" : "1960-01-01T00:01:30", "t" : "01:01:01"}));
+    assert_eq!(rows[1], json!({"x" : null, "name" : "", "day" : null, "at" : null, "t" : null}));
+    assert_eq!(rows[2]["x"], Value::Null);
+    assert_eq!(rows[2]["at"], "1959-12-31T23:59:59");
+}
+
+#[test]
+fn special_missing() {
+    let rows = lines(JsonOptions::default().special_missing(true));
+    assert_eq!(rows[1]["x"], Value::Null);
+    assert_eq!(rows[2]["x"], ".A");
+    assert_eq!(rows[2]["day"], "._");
+    assert_eq!(SasVal::Numeric(special(b'Z')).missing_tag(), Some('Z'));
+    assert_eq!(SasVal::Numeric(f64::NAN).missing_tag(), None);
+}
+
+#[test]
+fn document_with_labels() {
+    let bytes = fixture();
+    for labels in [false, true] {
+        let mut sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+        let mut out = Vec::new();
+        JsonOptions::default().labels(labels).write_document(&mut sas, &mut out).unwrap();
+        let doc : Value = serde_json::from_slice(&out).unwrap();
+        let schema = &doc["schema"];
+        assert_eq!(schema["rows"], 3);
+        assert_eq!(schema["encoding"], sas.encoding().name());
+        let types : Vec<&str> = schema["columns"].as_array().unwrap().iter().map(|x| x["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["number", "string", "date", "datetime", "time"]);
+        assert_eq!(schema["columns"][2]["format"], json!({"name" : "DATE", "width" : 9, "decimals" : 0}));
+        assert!(schema["columns"][1].get("format").is_none());
+        assert_eq!(schema["columns"][0].get("label").is_some(), labels);
+        if labels {
+            assert_eq!(schema["columns"][0]["label"], "Some number");
+        }
+        assert_eq!(doc["data"].as_array().unwrap().len(), 3);
+        assert_eq!(doc["data"][0]["name"], "one");
+    }
+}
+
+#[test]
+fn metadata_types() {
+    let bytes = fixture();
+    let sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+    let formats = serde_json::to_value(sas.col_format_specs()).unwrap();
+    assert_eq!(formats[4], json!({"name" : "TIME", "width" : 8, "decimals" : 0}));
+    assert_eq!(serde_json::to_value(sas.damage_report()).unwrap()["damaged"], json!([]));
+    assert_eq!(serde_json::to_value(Codec::Zstd).unwrap(), "zstd");
+}
+
+#[test]
+fn raw_text() {
+    let bytes = fixture();
+    let mut sas = ReadOptions::default().decode_policy(DecodePolicy::Raw).read(Cursor::new(&bytes)).unwrap();
+    let mut out = Vec::new();
+    JsonOptions::default().write_document(&mut sas, &mut out).unwrap();
+    let doc : Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(doc["schema"]["columns"][1]["type"], "bytes");
+    //an array of the bytes, padding included
+    assert_eq!(doc["data"][0]["name"], json!(b"one         "));
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "mmap")]
+use std::fs::File;
+use std::io::{Cursor, Read};
+use sas::*;
+use sas::fixture::*;
+
+fn fixture(compression : Compression) -> Fixture {
+    let mut f = Fixture{compression, layout : Layout::Mix, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("x"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+    ];
+    for i in 0..1000 {
+        f.rows.push(vec![Cell::Num(i as f64 / 3.0), Cell::Str(format!("row{i}")), Cell::Num(i as f64)]);
+    }
+    f
+}
+
+fn rows<R : Read>(sas : SAS7bdat<R>) -> String {
+    format!("{:?}", sas.map(|x| x.unwrap()).collect::<Vec<_>>())
+}
+
+#[test]
+fn same_rows_as_the_stream_reader() {
+    for compression in [Compression::None, Compression::Rle, Compression::Rdc] {
+        let f = fixture(compression);
+        let path = std::env::temp_dir().join(format!("sas_mmap_{compression:?}_{}.sas7bdat", std::process::id()));
+        std::fs::write(&path, f.to_bytes()).unwrap();
+        let mapped = unsafe { SAS7bdat::from_mmap(File::open(&path).unwrap()) }.map(rows);
+        let mapped_opts = unsafe { ReadOptions::default().include_deleted(true).map(File::open(&path).unwrap()) }.map(rows);
+        std::fs::remove_file(&path).unwrap();
+        let expected = rows(SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap());
+        assert_eq!(mapped.unwrap(), expected, "{compression:?}");
+        assert_eq!(mapped_opts.unwrap(), expected, "{compression:?}");
+    }
+}
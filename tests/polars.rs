@@ -0,0 +1,91 @@
+#![cfg(feature = "polars")]
+use polars::prelude::*;
+use sas::{DecodePolicy, ReadOptions};
+use sas::fixture::{Cell, Column, Compression, Fixture};
+use sas::polars::*;
+
+mod common;
+use common::TempPath;
+
+//300 rows, with every 7th x missing and times over a day from row 200 on.
+fn fixture(compression : Compression) -> Fixture {
+    let mut f = Fixture{compression, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("x").with_label("Some number"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+        Column::numeric("t").with_format("TIME", 8, 0),
+    ];
+    for i in 0..300 {
+        let x = if i % 7 == 3 { f64::NAN } else { i as f64 };
+        let t = if i < 200 { 60.0 } else { 90000.0 };
+        f.rows.push(vec![Cell::Num(x), Cell::Str(format!("row{i}")), Cell::Num(i as f64), Cell::Num(86400.0 + i as f64), Cell::Num(t)]);
+    }
+    f
+}
+
+fn write(name : &str, compression : Compression) -> TempPath {
+    TempPath::file(&format!("polars_{name}.sas7bdat"), &fixture(compression).to_bytes())
+}
+
+#[test]
+fn read_whole_file() {
+    let path = write("read", Compression::Rdc);
+    let df = read_sas7bdat(&path, &ReadOptions::default()).unwrap();
+    assert_eq!(df.shape(), (300, 5));
+    let dtypes : Vec<DataType> = df.dtypes();
+    assert_eq!(dtypes, vec![
+        DataType::Float64,
+        DataType::String,
+        DataType::Date,
+        DataType::Datetime(TimeUnit::Microseconds, None),
+        DataType::Duration(TimeUnit::Microseconds),
+    ]);
+    assert_eq!(df.column("x").unwrap().null_count(), 43);
+    assert_eq!(df.column("name").unwrap().str().unwrap().get(299), Some("row299"));
+    //1960-01-02 is 3652 days before 1970
+    assert_eq!(df.column("day").unwrap().date().unwrap().physical().get(1), Some(-3652));
+    assert_eq!(df.column("at").unwrap().datetime().unwrap().physical().get(0), Some((86400 - 315_619_200) * 1_000_000));
+    assert_eq!(df.column("t").unwrap().duration().unwrap().physical().get(0), Some(60_000_000));
+}
+
+#[test]
+fn scan_with_projection_and_limit() {
+    let path = write("scan", Compression::None);
+    let scan = SasScan::new(&path, ReadOptions::default()).unwrap();
+    assert_eq!(scan.labels()[0], "Some number");
+    assert_eq!(scan.schema().len(), 5);
+    let df = scan.finish().unwrap()
+        .select([col("name"), col("x")])
+        .limit(10)
+        .collect()
+        .unwrap();
+    assert_eq!(df.shape(), (10, 2));
+    assert_eq!(df.get_column_names(), ["name", "x"]);
+    assert_eq!(df.column("x").unwrap().f64().unwrap().get(9), Some(9.0));
+}
+
+#[test]
+fn truncated_file_fails() {
+    let mut bytes = fixture(Compression::None).to_bytes();
+    bytes.truncate(bytes.len() - 100);
+    let path = TempPath::file("polars_truncated.sas7bdat", &bytes);
+    let er = read_sas7bdat(&path, &ReadOptions::default()).unwrap_err();
+    assert!(er.to_string().contains("Truncated"), "{er}");
+    //the rows before the damage can still be read
+    let df = scan_sas7bdat(&path, ReadOptions::default()).unwrap().limit(10).collect().unwrap();
+    assert_eq!(df.height(), 10);
+}
+
+#[test]
+fn raw_text_and_long_times() {
+    let path = write("raw", Compression::Rle);
+    let df = read_sas7bdat(&path, &ReadOptions::default().decode_policy(DecodePolicy::Raw)).unwrap();
+    let name = df.column("name").unwrap();
+    assert_eq!(name.dtype(), &DataType::Binary);
+    //raw cells keep their padding
+    assert_eq!(name.binary().unwrap().get(7), Some(&b"row7        "[..]));
+    //a time of 25 hours stays a duration
+    assert_eq!(df.column("t").unwrap().duration().unwrap().physical().get(250), Some(90_000_000_000));
+}
@@ -0,0 +1,105 @@
+use std::io::Cursor;
+use sas::SAS7bdat;
+use sas::fixture::*;
+
+fn fixture() -> Vec<u8> {
+    let mut f = Fixture{page_len : 1024, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("id").with_label("Subject's id"),
+        Column::character("name", 4),
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+        Column::numeric("t").with_format("TIME", 8, 0),
+    ];
+    f.rows.push(vec![Cell::Num(1.5), Cell::Str("ab".into()), Cell::Num(0.0), Cell::Num(1.0), Cell::Num(3661.0)]);
+    f.rows.push(vec![Cell::Num(f64::NAN), Cell::Str(String::new()), Cell::Num(f64::NAN), Cell::Num(f64::NAN), Cell::Num(90000.0)]);
+    f.to_bytes()
+}
+
+#[test]
+fn create_table() {
+    let bytes = fixture();
+    let sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+    assert_eq!(sas::postgres::create_table(&sas, "visits"), "\
+CREATE TABLE \"visits\" (
+    \"id\" double precision,
+    \"name\" varchar(4),
+    \"day\" date,
+    \"at\" timestamp,
+    \"t\" interval
+);
+COMMENT ON COLUMN \"visits\".\"id\" IS 'Subject''s id';
+");
+}
+
+#[test]
+fn copy_binary() {
+    let bytes = fixture();
+    let mut sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(sas::postgres::write_copy(&mut sas, &mut out).unwrap(), 2);
+    let expected : &[&[u8]] = &[
+        b"PGCOPY\n\xff\r\n\0",
+        &[0, 0, 0, 0, 0, 0, 0, 0],
+        //1.5, "ab", 1960-01-01, 1960-01-01 00:00:01, 01:01:01
+        &[0, 5],
+        &[0, 0, 0, 8, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0],
+        &[0, 0, 0, 2, b'a', b'b'],
+        //-14610 days from 2000-01-01
+        &[0, 0, 0, 4, 0xff, 0xff, 0xc6, 0xee],
+        //-1262303999000000 microseconds
+        &[0, 0, 0, 8, 0xff, 0xfb, 0x83, 0xf0, 0xf2, 0x8a, 0x82, 0x40],
+        &[0, 0, 0, 16, 0, 0, 0, 0, 0xda, 0x36, 0x6d, 0x40, 0, 0, 0, 0, 0, 0, 0, 0],
+        //missing values, "" and 25:00:00
+        &[0, 5],
+        &[0xff, 0xff, 0xff, 0xff],
+        &[0, 0, 0, 0],
+        &[0xff, 0xff, 0xff, 0xff],
+        &[0xff, 0xff, 0xff, 0xff],
+        &[0, 0, 0, 16, 0, 0, 0, 0x14, 0xf4, 0x6b, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        &[0xff, 0xff],
+    ];
+    assert_eq!(out, expected.concat());
+}
+
+#[test]
+fn dates_out_of_range_are_null() {
+    let columns = vec![
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+    ];
+    let mut f = Fixture{columns, ..Fixture::default()};
+    f.rows.push(vec![Cell::Num(1e300), Cell::Num(-1e300)]);
+    let bytes = f.to_bytes();
+    let mut sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(sas::postgres::write_copy(&mut sas, &mut out).unwrap(), 1);
+    assert_eq!(&out[19..], [0, 2, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn columns_behind_an_empty_column() {
+    let columns = vec![Column::numeric("x"), Column::character("empty", 0), Column::numeric("y")];
+    let mut f = Fixture{columns, ..Fixture::default()};
+    f.rows.push(vec![Cell::Num(1.0), Cell::Str(String::new()), Cell::Num(2.0)]);
+    let bytes = f.to_bytes();
+    let mut sas = SAS7bdat::from_reader(Cursor::new(&bytes)).unwrap();
+    assert_eq!(sas::postgres::create_table(&sas, "t"), "\
+CREATE TABLE \"t\" (
+    \"x\" double precision,
+    \"empty\" varchar(1),
+    \"y\" varchar(1)
+);
+");
+    //every column has a field, the ones the reader does not decode are NULL
+    let mut out = Vec::new();
+    sas::postgres::write_copy(&mut sas, &mut out).unwrap();
+    let expected : &[&[u8]] = &[
+        &[0, 3],
+        &[0, 0, 0, 8, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0],
+        &[0xff, 0xff, 0xff, 0xff],
+        &[0xff, 0xff, 0xff, 0xff],
+        &[0xff, 0xff],
+    ];
+    assert_eq!(out[19..], expected.concat());
+}
@@ -0,0 +1,77 @@
+use std::io::{Cursor, Read};
+use sas::*;
+use sas::fixture::*;
+
+fn fixture(compression : Compression) -> Fixture {
+    let mut f = Fixture{compression, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![Column::numeric("x"), Column::character("name", 12)];
+    for i in 0..2000 {
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Str(format!("row{i}"))]);
+    }
+    f
+}
+
+fn rows<R : Read>(sas : SAS7bdat<R>) -> Result<Vec<Vec<SasVal>>, SasError> {
+    sas.collect()
+}
+
+//Hands out at most 7 bytes per read.
+struct Trickle(Cursor<Vec<u8>>);
+
+impl Read for Trickle {
+    fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(7);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+#[test]
+fn same_rows_as_without_prefetch() {
+    for compression in [Compression::None, Compression::Rle] {
+        let bytes = fixture(compression).to_bytes();
+        let expected = rows(SAS7bdat::from_reader(Cursor::new(bytes.clone())).unwrap()).unwrap();
+        for depth in [1, 4] {
+            let rdr = PrefetchReader::new(Trickle(Cursor::new(bytes.clone())), depth);
+            let got = rows(SAS7bdat::from_reader(rdr).unwrap()).unwrap();
+            assert_eq!(format!("{got:?}"), format!("{expected:?}"), "{compression:?}, depth {depth}");
+        }
+    }
+}
+
+#[test]
+fn truncated_and_invalid_input() {
+    let mut bytes = fixture(Compression::None).to_bytes();
+    bytes.truncate(bytes.len() - 100);
+    let er = rows(SAS7bdat::from_reader(PrefetchReader::new(Cursor::new(bytes), 2)).unwrap()).unwrap_err();
+    assert!(matches!(er, SasError::Truncated(_)), "{er:?}");
+    assert!(SAS7bdat::from_reader(PrefetchReader::new(Cursor::new(vec![1u8; 100]), 2)).is_err());
+    assert!(SAS7bdat::from_reader(PrefetchReader::new(Cursor::new(Vec::new()), 2)).is_err());
+}
+
+//The header and page lengths of a 32 bit little-endian file are at 196 and
+//200, the prefetch thread does not allocate a page of 2 GiB for them.
+#[test]
+fn lengths_from_a_corrupt_header() {
+    let mut f = fixture(Compression::None);
+    f.u64 = false;
+    for (off, msg) in [(196, "Invalid header length"), (200, "Invalid page length")] {
+        let mut bytes = f.to_bytes();
+        bytes[off..off + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        match SAS7bdat::from_reader(PrefetchReader::new(Cursor::new(bytes), 2)) {
+            Err(SasError::SasProperty(x)) => assert!(x.starts_with(msg), "{x}"),
+            Err(er) => panic!("{er:?}"),
+            Ok(_) => panic!("read a corrupt header"),
+        }
+    }
+}
+
+#[test]
+fn open_reader_prefetches_files() {
+    let bytes = fixture(Compression::Rle).to_bytes();
+    let expected = rows(SAS7bdat::from_reader(Cursor::new(bytes.clone())).unwrap()).unwrap();
+    let path = std::env::temp_dir().join(format!("sas_prefetch_{}.sas7bdat", std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let got = open_reader(&path, None, 4).and_then(|x| rows(ReadOptions::default().read(x)?));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(format!("{:?}", got.unwrap()), format!("{expected:?}"));
+}
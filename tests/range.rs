@@ -0,0 +1,150 @@
+use std::io::{self, Cursor};
+use sas::*;
+use sas::fixture::*;
+
+const PAGE_LEN : usize = 4096;
+//header length of 32 bit fixtures
+const HDR_LEN : usize = 1024;
+
+fn fixture() -> Fixture {
+    let mut f = Fixture{u64 : false, page_len : PAGE_LEN, ..Fixture::default()};
+    f.columns = vec![Column::numeric("x"), Column::character("name", 24)];
+    for i in 0..3000 {
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Str(format!("row{i}"))]);
+    }
+    f
+}
+
+fn rows<R : io::Read>(sas : SAS7bdat<R>) -> String {
+    format!("{:?}", sas.map(|x| x.unwrap()).collect::<Vec<_>>())
+}
+
+//Records the requested ranges.
+struct Counting{
+    bytes : Vec<u8>,
+    requests : Vec<(u64, usize)>,
+}
+
+impl RangeSource for &mut Counting{
+    fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize> {
+        self.requests.push((offset, buf.len()));
+        self.bytes.read_at(offset, buf)
+    }
+}
+
+#[test]
+fn page_aligned_requests() {
+    let bytes = fixture().to_bytes();
+    let pages = (bytes.len() - HDR_LEN) / PAGE_LEN;
+    let read_size = 5 * PAGE_LEN - 100;
+    let mut src = Counting{bytes : bytes.clone(), requests : Vec::new()};
+    let sas = ReadOptions::default().read(RangeReader::new(&mut src).with_read_size(read_size)).unwrap();
+    assert_eq!(rows(sas), rows(SAS7bdat::from_reader(Cursor::new(bytes)).unwrap()));
+
+    assert_eq!(src.requests[0], (0, 288));
+    assert_eq!(src.requests[1], (288, HDR_LEN - 288 + 5 * PAGE_LEN));
+    for (offset, len) in &src.requests[2..] {
+        assert_eq!((*offset as usize - HDR_LEN) % PAGE_LEN, 0, "{offset}");
+        assert_eq!(*len, 5 * PAGE_LEN);
+    }
+    //the header, then groups of 5 pages, and a last request finding the end
+    //of the file
+    assert!(src.requests.len() <= 2 + pages.div_ceil(5), "{:?}", src.requests);
+}
+
+#[test]
+fn requests_counted_by_the_reader() {
+    let bytes = fixture().to_bytes();
+    let mut rdr = RangeReader::new(bytes.as_slice()).with_read_size(8 << 20);
+    let mut all = Vec::new();
+    io::Read::read_to_end(&mut rdr, &mut all).unwrap();
+    assert_eq!(all, bytes);
+    //the start of the header, the rest of the file, the end of the file
+    assert!(rdr.requests() <= 3, "{}", rdr.requests());
+}
+
+//The header and page lengths are at 196 and 200, no request asks for the
+//2 GiB they claim.
+#[test]
+fn lengths_from_a_corrupt_header() {
+    for (off, msg) in [(196, "Invalid header length"), (200, "Invalid page length")] {
+        let mut bytes = fixture().to_bytes();
+        bytes[off..off + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        let mut src = Counting{bytes, requests : Vec::new()};
+        match ReadOptions::default().read(RangeReader::new(&mut src).with_read_size(PAGE_LEN)) {
+            Err(SasError::SasProperty(x)) => assert!(x.starts_with(msg), "{x}"),
+            Err(er) => panic!("{er:?}"),
+            Ok(_) => panic!("read a corrupt header"),
+        }
+        assert!(src.requests.iter().all(|x| x.1 <= PAGE_LEN), "{:?}", src.requests);
+    }
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    //Serves `bytes` on a local port, answering range requests unless
+    //`ranges` is false. Returns the url and the received Range headers.
+    fn serve(bytes : Vec<u8>, ranges : bool) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.sas7bdat", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut range = None;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(val) = line.strip_prefix("Range: bytes=") {
+                        let (start, end) = val.split_once('-').unwrap();
+                        range = Some((start.parse::<usize>().unwrap(), end.parse::<usize>().unwrap()));
+                        log.lock().unwrap().push(val.to_string());
+                    }
+                }
+                let (status, body) = match range {
+                    Some((start, _)) if ranges && start >= bytes.len() => ("416 Range Not Satisfiable", &bytes[..0]),
+                    Some((start, end)) if ranges => ("206 Partial Content", &bytes[start..(end + 1).min(bytes.len())]),
+                    _ => ("200 OK", &bytes[..]),
+                };
+                let head = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(body));
+            }
+        });
+        (url, seen)
+    }
+
+    #[test]
+    fn ureq_range_requests() {
+        let bytes = fixture().to_bytes();
+        let (url, seen) = serve(bytes.clone(), true);
+        let sas = ReadOptions::default().read_range(HttpRange::new(&url, UreqClient(ureq::Agent::new()))).unwrap();
+        assert_eq!(rows(sas), rows(SAS7bdat::from_reader(Cursor::new(bytes.clone())).unwrap()));
+        assert_eq!(seen.lock().unwrap()[0], "0-287");
+
+        let mut client = UreqClient(ureq::Agent::new());
+        assert_eq!(client.get_range(&url, 10, 20).unwrap(), &bytes[10..20]);
+        let len = bytes.len() as u64;
+        assert_eq!(client.get_range(&url, len - 4, len + 100).unwrap(), &bytes[bytes.len() - 4..]);
+        assert!(client.get_range(&url, len + 10, len + 20).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ureq_server_without_ranges() {
+        let bytes = fixture().to_bytes();
+        let (url, _) = serve(bytes.clone(), false);
+        let mut client = UreqClient(ureq::Agent::new());
+        //the start of the object is still the right answer
+        assert_eq!(client.get_range(&url, 0, 288).unwrap(), &bytes[..288]);
+        let er = client.get_range(&url, 4096, 8192).unwrap_err();
+        assert!(er.to_string().contains("does not support range requests"), "{er}");
+        assert!(SAS7bdat::from_range(HttpRange::new(&url, UreqClient(ureq::Agent::new()))).is_err());
+    }
+}
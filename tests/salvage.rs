@@ -0,0 +1,98 @@
+use std::io::Cursor;
+use sas::*;
+use sas::fixture::*;
+
+//A 32 bit little-endian file, so a page type is the two bytes at 16 of its
+//page and a data page has the type 0x0100.
+fn fixture(compression : Compression, rows : usize) -> Fixture {
+    let mut f = Fixture{compression, u64 : false, ..Fixture::default()};
+    f.columns = vec![Column::numeric("x"), Column::character("name", 40)];
+    for i in 0..rows {
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Str(format!("row{i}"))]);
+    }
+    f
+}
+
+fn read_x(sas : &mut SAS7bdat<Cursor<Vec<u8>>>) -> Vec<usize> {
+    let mut seen = Vec::new();
+    while sas.read_line().unwrap() {
+        if let SasVal::Numeric(x) = sas.row_vals[0] {
+            seen.push(x as usize);
+        }
+    }
+    seen
+}
+
+#[test]
+fn damaged_page() {
+    let mut bytes = fixture(Compression::None, 500).to_bytes();
+    let pages : Vec<usize> = (1024..bytes.len()).step_by(4096)
+        .filter(|x| bytes[x + 16..x + 18] == [0x00, 0x01])
+        .collect();
+    assert!(pages.len() > 2);
+    let page = pages[1];
+    let lost = u16::from_le_bytes([bytes[page + 18], bytes[page + 19]]) as usize;
+    let first = f64::from_le_bytes(bytes[page + 24..page + 32].try_into().unwrap()) as usize;
+    bytes[page + 16..page + 18].copy_from_slice(&[0x77, 0x77]);
+
+    let mut strict = ReadOptions::default().read(Cursor::new(bytes.clone())).unwrap();
+    assert!(std::iter::from_fn(|| Some(strict.read_line())).find_map(|x| x.err()).is_some());
+
+    let mut sas = ReadOptions::default().salvage(true).read(Cursor::new(bytes)).unwrap();
+    let seen = read_x(&mut sas);
+    let expected : Vec<usize> = (0..500).filter(|x| !(first..first + lost).contains(x)).collect();
+    assert_eq!(seen, expected);
+    let report = sas.damage_report();
+    assert_eq!(report.damaged.len(), 1);
+    let damage = &report.damaged[0];
+    assert_eq!(damage.page, (page - 1024) / 4096);
+    assert_eq!((damage.start, damage.end), (page as u64, page as u64 + 4096));
+    assert_eq!(damage.rows_lost, None);
+    assert_eq!(report.rows_lost, lost);
+}
+
+#[test]
+fn damaged_compressed_row() {
+    let f = fixture(Compression::Rle, 300);
+    let mut bytes = f.to_bytes();
+    let row = rle_compress(&f.encode_row(&f.rows[57]));
+    assert!(row.len() < f.row_len());
+    let off = bytes.windows(row.len()).position(|x| x == row.as_slice()).unwrap();
+    //a run of 4088 bytes, longer than the row
+    bytes[off..off + 2].copy_from_slice(&[0x4f, 0xe6]);
+
+    let mut strict = ReadOptions::default().read(Cursor::new(bytes.clone())).unwrap();
+    assert!(std::iter::from_fn(|| Some(strict.read_line())).find_map(|x| x.err()).is_some());
+
+    let mut sas = ReadOptions::default().salvage(true).read(Cursor::new(bytes)).unwrap();
+    let seen = read_x(&mut sas);
+    assert_eq!(seen, (0..300).filter(|x| *x != 57).collect::<Vec<usize>>());
+    let report = sas.damage_report();
+    assert_eq!(report.damaged.len(), 1);
+    let damage = &report.damaged[0];
+    assert_eq!(damage.page, (off - 1024) / 4096);
+    assert_eq!((damage.start, damage.end), (off as u64, (off + row.len()) as u64));
+    assert_eq!(damage.rows_lost, Some(1));
+    assert_eq!(report.rows_lost, 1);
+}
+
+#[test]
+fn truncated_file() {
+    let mut bytes = fixture(Compression::None, 500).to_bytes();
+    let len = bytes.len();
+    bytes.truncate(len - 1000);
+    let mut strict = ReadOptions::default().read(Cursor::new(bytes.clone())).unwrap();
+    let er = std::iter::from_fn(|| Some(strict.read_line())).find_map(|x| x.err()).unwrap();
+    assert!(matches!(er, SasError::Truncated(_)), "{er:?}");
+
+    let mut sas = ReadOptions::default().salvage(true).read(Cursor::new(bytes)).unwrap();
+    let seen = read_x(&mut sas);
+    assert_eq!(seen, (0..seen.len()).collect::<Vec<usize>>());
+    let report = sas.damage_report();
+    //the partial last page
+    let page = (len - 4096 - 1024) / 4096;
+    assert_eq!(report.damaged.len(), 1);
+    assert_eq!(report.damaged[0].page, page);
+    assert_eq!((report.damaged[0].start, report.damaged[0].end), ((len - 4096) as u64, (len - 1000) as u64));
+    assert_eq!(report.rows_lost, 500 - seen.len());
+}
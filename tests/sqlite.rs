@@ -0,0 +1,112 @@
+#![cfg(feature = "sqlite")]
+use std::io::Cursor;
+use rusqlite::Connection;
+use sas::SAS7bdat;
+use sas::fixture::*;
+use sas::sqlite::SqliteOptions;
+
+fn fixture(first : usize, rows : usize) -> Fixture {
+    let mut f = Fixture{compression : Compression::Rdc, page_len : 1024, ..Fixture::default()};
+    f.columns = vec![
+        Column::numeric("id").with_label("Subject"),
+        Column::character("name", 12),
+        Column::numeric("day").with_format("DATE", 9, 0),
+        Column::numeric("at").with_format("DATETIME", 19, 0),
+        Column::numeric("t").with_format("TIME", 8, 0),
+    ];
+    for i in first..first + rows {
+        let x = if i % 4 == 1 { f64::NAN } else { i as f64 };
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Str(format!("row{i}")), Cell::Num(x), Cell::Num(86400.0 + i as f64), Cell::Num(3600.0)]);
+    }
+    f
+}
+
+fn bytes(first : usize, rows : usize) -> Vec<u8> {
+    fixture(first, rows).to_bytes()
+}
+
+fn write(conn : &mut Connection, options : &SqliteOptions, table : &str, bytes : &[u8]) -> usize {
+    let mut sas = SAS7bdat::from_reader(Cursor::new(bytes)).unwrap();
+    options.write(conn, table, &mut sas).unwrap()
+}
+
+#[test]
+fn types_and_metadata() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    assert_eq!(write(&mut conn, &SqliteOptions::default().batch_size(7), "visits", &bytes(0, 50)), 50);
+    let types : Vec<String> = conn.prepare("SELECT type FROM pragma_table_info('visits')").unwrap()
+        .query_map([], |x| x.get(0)).unwrap().map(|x| x.unwrap()).collect();
+    assert_eq!(types, ["REAL", "TEXT", "DATE", "DATETIME", "TIME"]);
+    let row : (f64, String, String, String, String) = conn.query_row("SELECT * FROM visits WHERE id = 2", [], |x| {
+        Ok((x.get(0)?, x.get(1)?, x.get(2)?, x.get(3)?, x.get(4)?))
+    }).unwrap();
+    assert_eq!(row, (2.0, "row2".into(), "1960-01-03".into(), "1960-01-02 00:00:02".into(), "01:00:00".into()));
+    let nulls : usize = conn.query_row("SELECT count(*) FROM visits WHERE day IS NULL", [], |x| x.get(0)).unwrap();
+    assert_eq!(nulls, 13);
+    let day : String = conn.query_row("SELECT date(day, '+1 day') FROM visits WHERE id = 0", [], |x| x.get(0)).unwrap();
+    assert_eq!(day, "1960-01-02");
+    let meta : Vec<(String, Option<String>, Option<String>)> = conn.prepare("SELECT name, label, format FROM _sas_columns WHERE table_name = 'visits' ORDER BY position").unwrap()
+        .query_map([], |x| Ok((x.get(0)?, x.get(1)?, x.get(2)?))).unwrap().map(|x| x.unwrap()).collect();
+    assert_eq!(meta[0], ("id".into(), Some("Subject".into()), None));
+    assert_eq!(meta[2], ("day".into(), None, Some("DATE".into())));
+}
+
+#[test]
+fn append_and_replace() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let options = SqliteOptions::default();
+    write(&mut conn, &options, "visits", &bytes(0, 30));
+    write(&mut conn, &options, "visits", &bytes(30, 20));
+    write(&mut conn, &options, "other", &bytes(0, 5));
+    let count = |conn : &Connection, table : &str| -> usize {
+        conn.query_row(&format!("SELECT count(*) FROM {table}"), [], |x| x.get(0)).unwrap()
+    };
+    assert_eq!(count(&conn, "visits"), 50);
+    assert_eq!(count(&conn, "other"), 5);
+    assert_eq!(count(&conn, "_sas_columns"), 10);
+    write(&mut conn, &options.clone().replace(true), "visits", &bytes(0, 3));
+    assert_eq!(count(&conn, "visits"), 3);
+    assert_eq!(count(&conn, "_sas_columns"), 10);
+}
+
+#[test]
+fn long_times_as_text() {
+    let mut f = fixture(0, 4);
+    for (row, t) in f.rows.iter_mut().zip([90000.0, 86400.0, 360_000.5, -3600.0]) {
+        row[4] = Cell::Num(t);
+    }
+    let mut conn = Connection::open_in_memory().unwrap();
+    write(&mut conn, &SqliteOptions::default(), "visits", &f.to_bytes());
+    let times : Vec<String> = conn.prepare("SELECT t FROM visits").unwrap()
+        .query_map([], |x| x.get(0)).unwrap().map(|x| x.unwrap()).collect();
+    assert_eq!(times, ["25:00:00", "24:00:00", "100:00:00.500", "-01:00:00"]);
+}
+
+#[test]
+fn append_checks_the_columns() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    write(&mut conn, &SqliteOptions::default(), "visits", &bytes(0, 5));
+    let mut f = fixture(5, 5);
+    f.columns[1] = Column::numeric("name");
+    for row in f.rows.iter_mut() {
+        row[1] = Cell::Num(1.0);
+    }
+    let mut sas = SAS7bdat::from_reader(Cursor::new(f.to_bytes())).unwrap();
+    let er = SqliteOptions::default().write(&mut conn, "visits", &mut sas).unwrap_err();
+    assert!(matches!(&er, sas::SasError::SasProperty(x) if x.contains("name TEXT") && x.contains("name REAL")), "{er:?}");
+    //nothing was written, and the metadata is the one of the table
+    let rows : usize = conn.query_row("SELECT count(*) FROM visits", [], |x| x.get(0)).unwrap();
+    assert_eq!(rows, 5);
+    let kind : String = conn.query_row("SELECT type FROM _sas_columns WHERE table_name = 'visits' AND position = 1", [], |x| x.get(0)).unwrap();
+    assert_eq!(kind, "TEXT");
+}
+
+#[test]
+fn quoted_names() {
+    let mut f = fixture(0, 3);
+    f.columns[1] = Column::character("the \"name\"", 12);
+    let mut conn = Connection::open_in_memory().unwrap();
+    assert_eq!(write(&mut conn, &SqliteOptions::default(), "my visits", &f.to_bytes()), 3);
+    let name : String = conn.query_row("SELECT \"the \"\"name\"\"\" FROM \"my visits\" WHERE id = 1", [], |x| x.get(0)).unwrap();
+    assert_eq!(name, "row1");
+}
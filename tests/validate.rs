@@ -0,0 +1,93 @@
+use std::io::Cursor;
+use sas::*;
+use sas::fixture::*;
+
+//a 32 bit little-endian file, with the page length at byte 200 of the header
+fn fixture() -> Fixture {
+    let mut f = Fixture{u64 : false, endianness : Endianness::Little, ..Fixture::default()};
+    f.columns = vec![Column::numeric("x"), Column::character("name", 12)];
+    for i in 0..700 {
+        f.rows.push(vec![Cell::Num(i as f64), Cell::Str(format!("row{i}"))]);
+    }
+    f
+}
+
+fn checks(report : &ValidationReport) -> Vec<Check> {
+    report.issues.iter().map(|x| x.check).collect()
+}
+
+#[test]
+fn valid_file() {
+    let bytes = fixture().to_bytes();
+    let report = validate(bytes.as_slice());
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(report.file_len, bytes.len() as u64);
+    assert_eq!(report.pages, report.page_count);
+    assert_eq!((report.rows, report.row_count), (700, 700));
+    assert!(report.to_string().ends_with("\nvalid"), "{report}");
+    assert_eq!(report.to_json(), format!(
+        "{{\"valid\":true,\"file_len\":{},\"page_count\":{},\"pages\":{},\"row_count\":700,\"rows\":700,\"issues\":[]}}",
+        bytes.len(), report.page_count, report.pages));
+}
+
+#[test]
+fn not_a_sas_file() {
+    let report = validate(&b"name,x\nrow0,0\n"[..]);
+    assert_eq!(checks(&report), [Check::Magic]);
+}
+
+#[test]
+fn truncated_file() {
+    let mut bytes = fixture().to_bytes();
+    bytes.truncate(bytes.len() - 3000);
+    let report = validate(bytes.as_slice());
+    assert!(!report.is_valid());
+    assert!(checks(&report).contains(&Check::PageCount), "{report}");
+    assert!(report.rows < 700);
+    assert!(report.to_json().starts_with("{\"valid\":false,"));
+    assert!(report.to_json().contains("{\"check\":\"page_count\",\"page\":null,\"message\":\""), "{}", report.to_json());
+}
+
+#[test]
+fn page_length_out_of_bounds() {
+    for page_len in [0u32, 32, 1 << 30] {
+        let mut bytes = fixture().to_bytes();
+        bytes[200..204].copy_from_slice(&page_len.to_le_bytes());
+        let report = validate(bytes.as_slice());
+        assert_eq!(checks(&report), [Check::Header], "{report}");
+        assert!(report.issues[0].message.contains("page length"), "{report}");
+        match SAS7bdat::from_reader(Cursor::new(bytes)) {
+            Err(SasError::SasProperty(msg)) => assert!(msg.contains("page length"), "{msg}"),
+            res => panic!("{page_len}: {:?}", res.err()),
+        }
+    }
+}
+
+//The row size subheader is the first one of the file. Without it the rows
+//cannot be laid out, but the other subheaders are still checked.
+#[test]
+fn unknown_signature() {
+    let mut bytes = fixture().to_bytes();
+    let pos = bytes.windows(4).position(|x| x == [0xF7, 0xF7, 0xF7, 0xF7]).unwrap();
+    bytes[pos..pos + 4].copy_from_slice(&[1, 2, 3, 4]);
+    let report = validate(bytes.as_slice());
+    assert_eq!(report.issues[0].check, Check::Signature, "{report}");
+    assert_eq!(report.issues[0].page, Some(0));
+    assert!(report.issues[0].message.contains("01020304"), "{report}");
+    assert!(report.to_string().contains("[signature] page 0: "), "{report}");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_report() {
+    let mut bytes = fixture().to_bytes();
+    bytes.truncate(bytes.len() - 3000);
+    let report = validate(bytes.as_slice());
+    let json : serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["rows"], report.rows);
+    assert_eq!(json["issues"].as_array().unwrap().len(), report.issues.len());
+    assert_eq!(json["issues"][0]["check"], report.issues[0].check.name());
+    //the same fields as serde writes
+    assert_eq!(json, serde_json::to_value(&report).unwrap());
+}